@@ -0,0 +1,192 @@
+//! Configuration of bindgen from `[package.metadata.inwelling.clib.spec.<name>.bindgen]`.
+
+use crate::spec::BindgenOptions;
+
+use bindgen::{
+    Builder,
+    EnumVariation,
+    callbacks::{ItemInfo, ItemKind, MacroParsingBehavior, ParseCallbacks},
+};
+
+use regex::RegexSet;
+
+use std::{
+    collections::BTreeSet,
+    path::Path,
+};
+
+/// Prefix of the variables renamed by `BlocklistVar` to be blocklisted.
+const BLOCKED_VAR: &str = "__clib_blocked_var_";
+
+/// Blocks the variables matching `blocklist-var`, but not the functions and types
+/// matching it as `blocklist_item()` would: macros matching it are not parsed, and
+/// extern variables are renamed with `BLOCKED_VAR`, which is blocklisted. Static
+/// variables defined in headers are not seen by the callbacks of bindgen.
+#[derive( Debug )]
+struct BlocklistVar( RegexSet );
+
+impl ParseCallbacks for BlocklistVar {
+    fn will_parse_macro( &self, name: &str ) -> MacroParsingBehavior {
+        if self.0.is_match( name ) {
+            MacroParsingBehavior::Ignore
+        } else {
+            MacroParsingBehavior::Default
+        }
+    }
+
+    fn generated_name_override( &self, item_info: ItemInfo<'_> ) -> Option<String> {
+        (matches!( item_info.kind, ItemKind::Var ) && self.0.is_match( item_info.name ))
+            .then( || format!( "{}{}", BLOCKED_VAR, item_info.name ))
+    }
+}
+
+/// Creates a bindgen builder configured by `options`.
+pub fn builder( options: &BindgenOptions ) -> Builder {
+    let mut builder = Builder::default()
+        .generate_comments( options.doc_comments.unwrap_or( false ))
+    ;
+
+    for regex in &options.allowlist_function { builder = builder.allowlist_function( regex ); }
+    for regex in &options.allowlist_type     { builder = builder.allowlist_type    ( regex ); }
+    for regex in &options.allowlist_var      { builder = builder.allowlist_var     ( regex ); }
+    for regex in &options.blocklist_function { builder = builder.blocklist_function( regex ); }
+    for regex in &options.blocklist_type     { builder = builder.blocklist_type    ( regex ); }
+    for regex in &options.opaque_type        { builder = builder.opaque_type       ( regex ); }
+
+    if !options.blocklist_var.is_empty() {
+        // Anchored like the regexes of bindgen.
+        let regexes = options.blocklist_var.iter().map( |regex| format!( "^({})$", regex ));
+        let regexes = RegexSet::new( regexes ).expect( "regexes of `blocklist-var` should have been checked." );
+        builder = builder
+            .parse_callbacks( Box::new( BlocklistVar( regexes )))
+            .blocklist_item( format!( "{}.*", BLOCKED_VAR ));
+    }
+
+    if let Some( enum_style ) = &options.enum_style {
+        let enum_style = enum_style.parse::<EnumVariation>().expect( "enum style should have been checked." );
+        builder = builder.default_enum_style( enum_style );
+    }
+
+    if let Some( derive ) = &options.derive {
+        let has = |name: &str| derive.iter().any( |derive| derive == name );
+        builder = builder
+            .derive_debug     ( has( "Debug"      ))
+            .derive_default   ( has( "Default"    ))
+            .derive_copy      ( has( "Copy"       ))
+            .derive_hash      ( has( "Hash"       ))
+            .derive_partialeq ( has( "PartialEq"  ))
+            .derive_eq        ( has( "Eq"         ))
+            .derive_partialord( has( "PartialOrd" ))
+            .derive_ord       ( has( "Ord"        ))
+        ;
+    }
+
+    if let Some( size_t_is_usize ) = options.size_t_is_usize {
+        builder = builder.size_t_is_usize( size_t_is_usize );
+    }
+    if let Some( layout_tests ) = options.layout_tests {
+        builder = builder.layout_tests( layout_tests );
+    }
+
+    builder
+}
+
+/// Restricts the items generated for a library to those declared in its `headers`,
+/// or in their directories below the include root, e.g. "/usr/include/tcl8.6" but
+/// not "/usr/include", shared with other libraries. A library having allowlists of
+/// its own is restricted by them instead. Its `allowlist-path` adds more files.
+/// Without `include_dir`, e.g. resolved by cmake, the library is not restricted.
+pub fn restrict( mut builder: Builder, include_dir: &str, headers: &[String], include_root: &[String], options: &BindgenOptions ) -> Builder {
+    if include_dir.is_empty() {
+        return builder;
+    }
+    let include_dir = Path::new( include_dir );
+
+    if options.allowlist_function.is_empty()
+        && options.allowlist_type.is_empty()
+        && options.allowlist_var.is_empty()
+    {
+        let root = root_of( include_dir, include_root );
+        let regexes = headers
+            .iter()
+            .map( |header| match Path::new( header ).parent() {
+                Some( dir ) if dir.starts_with( root ) && dir != root => path_regex( dir ),
+                _ => escape_regex( header ),
+            })
+            .collect::<BTreeSet<_>>();
+        for regex in regexes {
+            builder = builder.allowlist_file( regex );
+        }
+    }
+    for path in &options.allowlist_path {
+        builder = builder.allowlist_file( path_regex( &include_dir.join( path )));
+    }
+    builder
+}
+
+/// The nearest one of `include_dir` and its ancestors ending with any of `include_root`,
+/// e.g. "/usr/include" for "/usr/include/tcl8.6", or else `include_dir` itself.
+fn root_of<'a>( include_dir: &'a Path, include_root: &[String] ) -> &'a Path {
+    include_dir
+        .ancestors()
+        .find( |dir| include_root.iter().any( |root| dir.ends_with( root )))
+        .unwrap_or( include_dir )
+}
+
+/// Matches the file of `path`, or any file under the directory of `path`.
+fn path_regex( path: &Path ) -> String {
+    let path = path.to_string_lossy();
+    format!( "{}([/\\\\].*)?", escape_regex( path.trim_end_matches( ['/', '\\'] )))
+}
+
+pub fn escape_regex( text: &str ) -> String {
+    let mut escaped = String::with_capacity( text.len() );
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$#&-~".contains( c ) {
+            escaped.push( '\\' );
+        }
+        escaped.push( c );
+    }
+    escaped
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_macros_matching_blocklist_var() {
+        let callbacks = BlocklistVar( RegexSet::new([ "^(TCL_.*)$" ]).unwrap() );
+        assert!( matches!( callbacks.will_parse_macro( "TCL_OK" ), MacroParsingBehavior::Ignore ));
+        assert!( matches!( callbacks.will_parse_macro( "MY_TCL_OK" ), MacroParsingBehavior::Default ));
+    }
+
+    fn allowlisted_files( include_dir: &str, headers: &[&str] ) -> Vec<String> {
+        let headers = headers.iter().map( |header| header.to_string() ).collect::<Vec<_>>();
+        let include_root = [ "include".to_owned() ];
+        let flags = restrict( Builder::default(), include_dir, &headers, &include_root, &BindgenOptions::default() )
+            .command_line_flags();
+        flags
+            .iter()
+            .zip( flags.iter().skip( 1 ))
+            .filter( |(flag, _)| *flag == "--allowlist-file" )
+            .map( |(_, regex)| regex.clone() )
+            .collect()
+    }
+
+    #[test]
+    fn allowlists_headers_in_include_root() {
+        assert_eq!( allowlisted_files( "/usr/include", &[ "/usr/include/zlib.h", "/usr/include/libxml/parser.h" ]), vec![
+            "/usr/include/libxml([/\\\\].*)?".to_owned(),
+            "/usr/include/zlib\\.h".to_owned(),
+        ]);
+        assert_eq!( allowlisted_files( "/usr/include/tcl8.6", &[ "/usr/include/tcl8.6/tcl.h" ]), vec![
+            "/usr/include/tcl8\\.6([/\\\\].*)?".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn does_not_restrict_without_include_dir() {
+        assert!( allowlisted_files( "", &[ "tk.h" ]).is_empty() );
+    }
+}
@@ -0,0 +1,202 @@
+//! Probing libraries installing only CMake package config files, e.g. FooConfig.cmake,
+//! by `find_package()` in a throwaway project.
+
+use crate::{
+    UTF8_PATH,
+    report,
+    spec::CMakePackage,
+};
+
+use anyhow::{Context, Result, anyhow};
+
+use std::{
+    collections::HashSet,
+    env,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Environment variables affecting the result of `find_package()`.
+const ENV_VARS: &[&str] = &[ "CMAKE", "CMAKE_PREFIX_PATH" ];
+
+/// Collects usage requirements of the targets, and of the targets they link
+/// recursively, into "clib-probe.txt". Old-style packages without targets are
+/// told by their `<Package>_INCLUDE_DIRS`, `<Package>_DEFINITIONS` and
+/// `<Package>_LIBRARIES` variables instead.
+const CMAKE_LISTS: &str = r#"cmake_minimum_required( VERSION 3.10 )
+project( clib_probe LANGUAGES C )
+find_package( @PACKAGE@ REQUIRED @COMPONENTS@ )
+
+set( pending @TARGETS@ )
+set( visited )
+set( includes )
+set( defines )
+set( links )
+
+if( pending )
+    while( pending )
+        list( GET pending 0 target )
+        list( REMOVE_AT pending 0 )
+        if( target IN_LIST visited )
+            continue()
+        endif()
+        list( APPEND visited ${target} )
+        if( NOT TARGET ${target} )
+            message( FATAL_ERROR "@PACKAGE@ does not provide target ${target}" )
+        endif()
+
+        list( APPEND includes "$<TARGET_PROPERTY:${target},INTERFACE_INCLUDE_DIRECTORIES>" )
+        list( APPEND defines "$<TARGET_PROPERTY:${target},INTERFACE_COMPILE_DEFINITIONS>" )
+        get_target_property( type ${target} TYPE )
+        if( type MATCHES "^(STATIC|SHARED)_LIBRARY$" )
+            list( APPEND links "$<TARGET_LINKER_FILE:${target}>" )
+        elseif( type STREQUAL "UNKNOWN_LIBRARY" )
+            list( APPEND links "$<TARGET_FILE:${target}>" )
+        endif()
+
+        get_target_property( deps ${target} INTERFACE_LINK_LIBRARIES )
+        if( deps )
+            foreach( dep IN LISTS deps )
+                string( REGEX REPLACE "^\\$<LINK_ONLY:(.*)>$" "\\1" dep "${dep}" )
+                if( TARGET ${dep} )
+                    list( APPEND pending ${dep} )
+                else()
+                    list( APPEND links "${dep}" )
+                endif()
+            endforeach()
+        endif()
+    endwhile()
+else()
+    set( includes ${@PACKAGE@_INCLUDE_DIRS} ${@PACKAGE@_INCLUDE_DIR} )
+    set( defines ${@PACKAGE@_DEFINITIONS} )
+    set( links ${@PACKAGE@_LIBRARIES} ${@PACKAGE@_LIBRARY} )
+endif()
+
+file( GENERATE OUTPUT "${CMAKE_BINARY_DIR}/clib-probe.txt" CONTENT
+"version=${@PACKAGE@_VERSION}
+include=${includes}
+define=${defines}
+link=${links}
+" )
+"#;
+
+/// What `find_package()` says about a library.
+#[derive( Debug, Default )]
+pub struct Package {
+    pub version      : Option<String>,
+    pub include_dirs : Vec<String>,
+    pub cflags       : Vec<String>,
+    pub libs         : Vec<String>, // in the form of `Libs` of .pc files
+}
+
+/// Finds the package by a locally installed cmake, or the one in `CMAKE`.
+pub fn probe( pkg_name: &str, cmake: &CMakePackage ) -> Result<Package> {
+    for var in ENV_VARS {
+        report::emit( format!( "rerun-if-env-changed={}", var ));
+    }
+
+    let out_dir = PathBuf::from( env::var( "OUT_DIR" ).expect( "$OUT_DIR should exist." ));
+    let project_dir = out_dir.join( "cmake" ).join( pkg_name );
+    let build_dir = project_dir.join( "build" );
+    fs::create_dir_all( &build_dir )?;
+
+    let components = if cmake.components.is_empty() {
+        String::new()
+    } else {
+        format!( "COMPONENTS {}", cmake.components.join( " " ))
+    };
+    let cmake_lists = CMAKE_LISTS
+        .replace( "@PACKAGE@", &cmake.package )
+        .replace( "@COMPONENTS@", &components )
+        .replace( "@TARGETS@", &cmake.targets.join( " " ));
+    fs::write( project_dir.join( "CMakeLists.txt" ), cmake_lists )?;
+
+    let program = env::var_os( "CMAKE" ).unwrap_or_else( || "cmake".into() );
+    let output = Command::new( &program )
+        .arg( &project_dir )
+        .current_dir( &build_dir )
+        .output()
+        .with_context( || format!( "failed to run {:?}", program ))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy( &output.stderr );
+        let message = stderr
+            .lines()
+            .map( str::trim )
+            .filter( |line| !line.is_empty() && !line.starts_with( "CMake Error" ))
+            .collect::<Vec<_>>()
+            .join( " " );
+        return Err( anyhow!( "cmake failed to find package {}: {}", cmake.package, message ));
+    }
+
+    let report = fs::read_to_string( build_dir.join( "clib-probe.txt" ))?;
+    let mut package = Package::default();
+    for line in report.lines() {
+        let Some(( key, value )) = line.split_once( '=' ) else { continue; };
+        let items = value.split( ';' ).filter( |item| !item.is_empty() );
+        match key {
+            "version" => package.version = Some( value.to_owned() ).filter( |version| !version.is_empty() ),
+            "include" => package.include_dirs.extend( items.map( ToOwned::to_owned )),
+            "define"  => package.cflags.extend( items.map( |define| format!( "-D{}", define ))),
+            "link"    => {
+                for item in items {
+                    package.libs.extend( link_words( item )? );
+                }
+            },
+            _ => (),
+        }
+    }
+
+    let mut seen = HashSet::new();
+    package.include_dirs.retain( |dir| seen.insert( dir.clone() ));
+    package.cflags.retain( |flag| seen.insert( flag.clone() ));
+    package.cflags.splice( 0..0, package.include_dirs.iter().map( |dir| format!( "-I{}", dir )));
+    Ok( package )
+}
+
+/// Turns an item of the link libraries into the words of `Libs` of .pc files:
+/// "/usr/lib/libz.so" into "-L/usr/lib" and "-lz", and "m" into "-lm". Other files,
+/// e.g. "libz.so.1", are kept as paths and linked as they are.
+fn link_words( item: &str ) -> Result<Vec<String>> {
+    if item.starts_with( '-' ) {
+        return Ok( item.split_whitespace().map( ToOwned::to_owned ).collect() );
+    }
+
+    let path = Path::new( item );
+    if !path.is_absolute() {
+        return Ok( vec![ format!( "-l{}", item )]);
+    }
+
+    let file_name = path.file_name().and_then( |name| name.to_str() ).context( UTF8_PATH )?;
+    let link_name = [ ".so", ".a", ".dylib", ".tbd" ]
+        .iter()
+        .find_map( |ext| file_name.strip_prefix( "lib" )?.strip_suffix( ext ))
+        .or_else( || file_name.strip_suffix( ".lib" ));
+    match (link_name, path.parent()) {
+        (Some( link_name ), Some( dir )) => Ok( vec![
+            format!( "-L{}", dir.to_str().context( UTF8_PATH )? ),
+            format!( "-l{}", link_name ),
+        ]),
+        _ => Ok( vec![ item.to_owned() ]),
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_items_into_link_words() {
+        for (item, words) in [
+            ("/usr/lib/libz.so"     , &[ "-L/usr/lib", "-lz" ][..]),
+            ("/opt/tcl/lib/libtcl.a", &[ "-L/opt/tcl/lib", "-ltcl" ]),
+            ("/usr/lib/libz.so.1"   , &[ "/usr/lib/libz.so.1" ]),
+            ("/usr/lib/z.lib"       , &[ "-L/usr/lib", "-lz" ]),
+            ("m"                    , &[ "-lm" ]),
+            ("-framework Cocoa"     , &[ "-framework", "Cocoa" ]),
+            ("-lpthread"            , &[ "-lpthread" ]),
+        ] {
+            assert_eq!( link_words( item ).unwrap(), words, "{}", item );
+        }
+    }
+}
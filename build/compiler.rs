@@ -0,0 +1,110 @@
+//! Directories searched by the system C compiler for headers and libraries.
+
+use crate::report;
+
+use anyhow::{Context, Result, anyhow};
+
+use std::{
+    env,
+    ffi::OsString,
+    path::PathBuf,
+    process::Command,
+    sync::OnceLock,
+};
+
+/// Environment variables affecting the directories searched by the compiler.
+const ENV_VARS: &[&str] = &[ "CC", "CPATH", "C_INCLUDE_PATH", "LIBRARY_PATH" ];
+
+/// Where the compiler looks for headers and libraries, in the order of searching.
+#[derive( Debug )]
+pub struct SearchDirs {
+    pub include_dirs : Vec<PathBuf>,
+    pub lib_dirs     : Vec<PathBuf>,
+}
+
+/// Asks the compiler for its search directories, once for all the libraries.
+pub fn search_dirs() -> Result<&'static SearchDirs> {
+    static SEARCH_DIRS: OnceLock<Result<SearchDirs,String>> = OnceLock::new();
+
+    SEARCH_DIRS
+        .get_or_init( || query().map_err( |err| err.to_string() ))
+        .as_ref()
+        .map_err( |err| anyhow!( "{}", err ))
+}
+
+/// The compiler in `CC_<target>` or `CC`, or else "cc".
+fn compiler() -> OsString {
+    let target_var = format!( "CC_{}", env::var( "TARGET" ).unwrap_or_default().replace( '-', "_" ));
+    report::emit( format!( "rerun-if-env-changed={}", target_var ));
+    env::var_os( target_var )
+        .or_else( || env::var_os( "CC" ))
+        .filter( |cc| !cc.is_empty() )
+        .unwrap_or_else( || "cc".into() )
+}
+
+fn query() -> Result<SearchDirs> {
+    for var in ENV_VARS {
+        report::emit( format!( "rerun-if-env-changed={}", var ));
+    }
+    if env::var( "CARGO_CFG_TARGET_ENV" ).is_ok_and( |target_env| target_env == "msvc" ) {
+        return Err( anyhow!( "the MSVC compiler does not tell its search directories" ));
+    }
+
+    let cc = compiler();
+    let run = |args: &[&str]| -> Result<String> {
+        let output = Command::new( &cc )
+            .args( args )
+            .output()
+            .with_context( || format!( "failed to run {:?}", cc ))?;
+        if output.status.success() {
+            // `-v` writes to stderr, while `-print-search-dirs` writes to stdout.
+            Ok( String::from_utf8_lossy( &output.stderr ).into_owned() + &String::from_utf8_lossy( &output.stdout ))
+        } else {
+            Err( anyhow!( "`{:?} {}` failed: {}", cc, args.join( " " ), String::from_utf8_lossy( &output.stderr ).trim() ))
+        }
+    };
+
+    let mut include_dirs = env_paths( "CPATH" );
+    include_dirs.extend( env_paths( "C_INCLUDE_PATH" ));
+    let verbose = run( &[ "-E", "-v", "-x", "c", "-o", null_device(), null_device() ])?;
+    include_dirs.extend( verbose
+        .lines()
+        .skip_while( |line| !line.ends_with( "search starts here:" ))
+        .take_while( |line| !line.starts_with( "End of search list." ))
+        .filter( |line| line.starts_with( ' ' ))
+        .map( |line| PathBuf::from( line.trim().trim_end_matches( " (framework directory)" ))));
+
+    let mut lib_dirs = env_paths( "LIBRARY_PATH" );
+    let search_dirs = run( &[ "-print-search-dirs" ])?;
+    if let Some( libraries ) = search_dirs.lines().find_map( |line| line.strip_prefix( "libraries: =" )) {
+        lib_dirs.extend( env::split_paths( libraries ));
+    }
+
+    Ok( SearchDirs {
+        include_dirs : existing_dirs( include_dirs ),
+        lib_dirs     : existing_dirs( lib_dirs ),
+    })
+}
+
+fn env_paths( name: &str ) -> Vec<PathBuf> {
+    env::var_os( name )
+        .map( |paths| env::split_paths( &paths ).collect() )
+        .unwrap_or_default()
+}
+
+/// Canonical paths of the directories, without duplicates and non-existing ones.
+fn existing_dirs( dirs: Vec<PathBuf> ) -> Vec<PathBuf> {
+    let mut existing = Vec::<PathBuf>::new();
+    for dir in dirs.into_iter().filter( |dir| !dir.as_os_str().is_empty() ) {
+        if let Ok( dir ) = dir.canonicalize() {
+            if dir.is_dir() && !existing.contains( &dir ) {
+                existing.push( dir );
+            }
+        }
+    }
+    existing
+}
+
+fn null_device() -> &'static str {
+    if cfg!( windows ) { "NUL" } else { "/dev/null" }
+}
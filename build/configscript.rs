@@ -0,0 +1,172 @@
+//! Probing libraries by their config scripts, e.g. `curl-config --cflags --libs`,
+//! or by shell scripts defining variables, e.g. tclConfig.sh.
+
+use crate::{
+    pkgconf,
+    spec::ConfigFile,
+    version,
+};
+
+use anyhow::{Context, Result, anyhow};
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    process::Command,
+};
+
+/// What a config script or file says about a library.
+#[derive( Debug, Default )]
+pub struct Flags {
+    pub version : Option<String>,
+    pub cflags  : Vec<String>,
+    pub libs    : Vec<String>,
+}
+
+/// Runs the config script with `--cflags`, `--libs` and `--version`.
+pub fn run( script: &Path ) -> Result<Flags> {
+    let query = |option: &str| -> Result<String> {
+        let output = Command::new( script )
+            .arg( option )
+            .output()
+            .with_context( || format!( "failed to run {}", script.display() ))?;
+        if output.status.success() {
+            Ok( String::from_utf8_lossy( &output.stdout ).into_owned() )
+        } else {
+            Err( anyhow!( "`{} {}` failed: {}", script.display(), option, String::from_utf8_lossy( &output.stderr ).trim() ))
+        }
+    };
+
+    Ok( Flags {
+        cflags  : pkgconf::split_words( &query( "--cflags" )? ),
+        libs    : pkgconf::split_words( &query( "--libs" )? ),
+        version : query( "--version" ).ok().and_then( |output| version::find_version( &output )),
+    })
+}
+
+/// Reads the variables of the config file, picking the ones named in `config_file`.
+/// `libs-private` are linked too, if `statik`.
+pub fn read( path: &Path, config_file: &ConfigFile, statik: bool ) -> Result<Flags> {
+    let text = fs::read_to_string( path ).with_context( || format!( "failed to read {:?}", path ))?;
+    let variables = variables( &text );
+
+    let words = |names: &[String]| names
+        .iter()
+        .flat_map( |name| pkgconf::split_words( variables.get( name ).map( String::as_str ).unwrap_or_default() ))
+        .collect::<Vec<_>>();
+
+    let mut libs = words( &config_file.libs );
+    if statik {
+        libs.extend( words( &config_file.libs_private ));
+    }
+
+    let version = config_file.version
+        .iter()
+        .filter_map( |name| variables.get( name ))
+        .map( String::as_str )
+        .collect::<String>();
+
+    Ok( Flags {
+        version : Some( version ).filter( |version| !version.is_empty() ),
+        cflags  : words( &config_file.cflags ),
+        libs    ,
+    })
+}
+
+/// Parses assignments like `NAME='value'`, `NAME="value with ${OTHER}"` and
+/// `NAME=value`, ignoring other lines.
+fn variables( text: &str ) -> HashMap<String,String> {
+    let mut variables = HashMap::<String,String>::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        let line = line.strip_prefix( "export " ).unwrap_or( line );
+        let Some(( name, value )) = line.split_once( '=' ) else { continue; };
+        if name.is_empty() || !name.chars().all( |c| c.is_ascii_alphanumeric() || c == '_' ) {
+            continue;
+        }
+
+        let value = if let Some( quoted ) = value.strip_prefix( '\'' ) {
+            quoted.strip_suffix( '\'' ).unwrap_or( quoted ).to_owned()
+        } else {
+            let value = value.strip_prefix( '"' ).map( |quoted| quoted.strip_suffix( '"' ).unwrap_or( quoted )).unwrap_or( value );
+            expand( value, &variables )
+        };
+        variables.insert( name.to_owned(), value );
+    }
+    variables
+}
+
+/// Interpolates `${NAME}` and `$NAME` with variables defined so far, or nothing.
+fn expand( value: &str, variables: &HashMap<String,String> ) -> String {
+    let mut expanded = String::with_capacity( value.len() );
+    let mut rest = value;
+
+    while let Some( dollar ) = rest.find( '$' ) {
+        expanded.push_str( &rest[ ..dollar ]);
+        rest = &rest[ dollar+1.. ];
+        let (name, after) = match rest.strip_prefix( '{' ) {
+            Some( braced ) => match braced.find( '}' ) {
+                Some( end ) => (&braced[ ..end ], &braced[ end+1.. ]),
+                None => (braced, ""),
+            },
+            None => {
+                let end = rest.find( |c: char| !c.is_ascii_alphanumeric() && c != '_' ).unwrap_or( rest.len() );
+                (&rest[ ..end ], &rest[ end.. ])
+            },
+        };
+        if name.is_empty() {
+            expanded.push( '$' );
+        } else if let Some( value ) = variables.get( name ) {
+            expanded.push_str( value );
+        }
+        rest = after;
+    }
+    expanded.push_str( rest );
+    expanded
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_variables() {
+        let variables = variables( r#"
+            # tclConfig.sh
+            TCL_VERSION='8.6'
+            TCL_PREFIX="/usr"
+            export TCL_LIB_SPEC="-L${TCL_PREFIX}/lib -ltcl$TCL_VERSION"
+            TCL_STUB_LIB_FLAG=-ltclstub8.6
+            TCL_QUOTED='${TCL_PREFIX}'
+            if test -n "$X"; then
+            BAD-NAME=x
+        "# );
+        for (name, value) in [
+            ("TCL_VERSION"      , Some( "8.6" )),
+            ("TCL_PREFIX"       , Some( "/usr" )),
+            ("TCL_LIB_SPEC"     , Some( "-L/usr/lib -ltcl8.6" )),
+            ("TCL_STUB_LIB_FLAG", Some( "-ltclstub8.6" )),
+            ("TCL_QUOTED"       , Some( "${TCL_PREFIX}" )),
+            ("BAD-NAME"         , None ),
+        ] {
+            assert_eq!( variables.get( name ).map( String::as_str ), value, "{}", name );
+        }
+    }
+
+    #[test]
+    fn expands_variables() {
+        let variables = HashMap::from([ ("A".to_owned(), "1".to_owned()), ("A_B".to_owned(), "2".to_owned()) ]);
+        for (value, expanded) in [
+            ("${A}x"   , "1x"),
+            ("$A_B/$A" , "2/1"),
+            ("$A-$C."  , "1-."),
+            ("${A"     , "1"),
+            ("$ $"     , "$ $"),
+            ("no vars" , "no vars"),
+        ] {
+            assert_eq!( expand( value, &variables ), expanded, "{}", value );
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Metadata of the resolved libraries for downstream code: `OUT_DIR/libs.rs`,
+//! included by the `clib::libs` module, which reads versions from `rustc-env`
+//! variables such as `CLIB_TCL86_VERSION`, and cfgs such as `clib_tcl86` and
+//! `clib_tcl86_ge_8_6`, passed to downstream build scripts as `DEP_CLIB_CFGS`.
+
+use crate::{
+    modules,
+    overrides,
+    probe::LibInfo,
+    report,
+    version,
+};
+
+use anyhow::{Result, anyhow};
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    fs,
+    path::Path,
+};
+
+/// Generates `libs.rs` in `out_path`, with a `Lib` const for each of `lib_names`,
+/// of which `linked` have their link directives emitted.
+pub fn generate( lib_info: &LibInfo, lib_names: &[String], linked: &[String], out_path: &Path ) -> Result<()> {
+    let resolved = lib_info.resolved.borrow();
+    let mut contents = String::new();
+    let mut consts = Vec::new();
+    let mut const_of = BTreeMap::<String,&String>::new(); // const name -> library
+
+    for lib_name in lib_names {
+        let Some( lib ) = resolved.get( lib_name ) else { continue; };
+        let const_name = const_name( lib_name );
+        if const_name == "ALL" {
+            return Err( anyhow!( "library {} has const name `ALL` in `clib::libs`, which is reserved for all the libraries", lib_name ));
+        }
+        if let Some( other ) = const_of.insert( const_name.clone(), lib_name ) {
+            return Err( anyhow!( "libraries {} and {} have the same const name `{}` in `clib::libs`", other, lib_name, const_name ));
+        }
+
+        let version = match &lib.version {
+            Some( version ) => {
+                let var = format!( "CLIB_{}_VERSION", overrides::env_name( lib_name ));
+                report::emit( format!( "rustc-env={}={}", var, version ));
+                format!( "Some( env!( {:?} ))", var )
+            },
+            None => "None".to_owned(),
+        };
+
+        let mut include_dirs = Vec::<&String>::new();
+        for dir in std::iter::once( &lib.include_dir ).chain( &lib.include_paths ) {
+            if !dir.is_empty() && !include_dirs.contains( &dir ) {
+                include_dirs.push( dir );
+            }
+        }
+        // e.g. "rustc-link-lib=static=z" links "z"
+        let link_libs = lib.link_directives
+            .iter()
+            .filter_map( |directive| directive.strip_prefix( "rustc-link-lib=" ))
+            .filter_map( |value| value.rsplit( '=' ).next() )
+            .collect::<Vec<_>>();
+        let linked_statically = linked.contains( lib_name ) && lib.links_static_archive();
+
+        writeln!( contents, "/// The library {}, found by {}.", lib_name, lib.found_by )?;
+        writeln!( contents, "pub const {}: Lib = Lib {{", const_name )?;
+        writeln!( contents, "    name              : {:?},", lib_name )?;
+        writeln!( contents, "    version           : {},", version )?;
+        writeln!( contents, "    pc_name           : {:?},", lib.pc_name )?;
+        writeln!( contents, "    found_by          : {:?},", lib.found_by )?;
+        writeln!( contents, "    found_in          : {:?},", lib.found_in )?;
+        writeln!( contents, "    include_dirs      : &{:?},", include_dirs )?;
+        writeln!( contents, "    link_libs         : &{:?},", link_libs )?;
+        writeln!( contents, "    linked_statically : {:?},", linked_statically )?;
+        writeln!( contents, "}};\n" )?;
+        consts.push( const_name );
+    }
+
+    write( out_path, contents, &consts )
+}
+
+/// The name of the `Lib` const of the library, e.g. "LIBXML_2_0" for "libxml-2.0".
+fn const_name( lib_name: &str ) -> String {
+    modules::module_name( lib_name ).to_uppercase()
+}
+
+/// Generates `libs.rs` in `out_path` without any library, if nothing is built.
+pub fn generate_empty( out_path: &Path ) -> Result<()> {
+    write( out_path, String::new(), &[] )
+}
+
+fn write( out_path: &Path, mut contents: String, consts: &[String] ) -> Result<()> {
+    writeln!( contents, "/// All the libraries resolved, each following its dependencies." )?;
+    writeln!( contents, "pub const ALL: &[Lib] = &[{}];", consts.join( ", " ))?;
+    fs::write( out_path.join( "libs.rs" ), contents )?;
+    Ok(())
+}
+
+/// Emits `clib_<name>` for each of `lib_names`, and `clib_<name>_ge_<version>` for
+/// each of its `version-cfgs` not newer than the version resolved. These cfgs, and
+/// the ones of the libraries in specs which are not resolved, are declared by
+/// `rustc-check-cfg`. Libraries of `for_docs`, served from `for-docs-rs` in docs-only
+/// builds, get them too, with the version cfgs ensured by the `version` of their specs.
+///
+/// Since cfgs apply to clib only, they are also emitted as the metadata `cfgs` and
+/// `check_cfgs`, space separated, which the build scripts of downstream crates read
+/// from `DEP_CLIB_CFGS` and `DEP_CLIB_CHECK_CFGS` to emit them again. So are the
+/// versions resolved, e.g. "tcl86=8.6.13" in `DEP_CLIB_VERSIONS`, for comparing
+/// with versions not in `version-cfgs`.
+pub fn emit_cfgs( lib_info: &LibInfo, lib_names: &[String], for_docs: &[&String] ) {
+    let resolved = lib_info.resolved.borrow();
+    let all_names = lib_info.specs.keys().chain( lib_names ).collect::<BTreeSet<_>>();
+    let mut cfgs = Vec::new();
+    let mut check_cfgs = Vec::new();
+    let mut versions = Vec::new();
+
+    for lib_name in all_names {
+        let cfg = cfg_name( lib_name );
+        let version_cfgs = lib_info.specs
+            .get( lib_name )
+            .map( |spec| spec.version_cfgs.as_slice() )
+            .unwrap_or_default()
+            .iter()
+            .map( |version| (version, format!( "{}_ge_{}", cfg, version.replace( '.', "_" ))))
+            .collect::<Vec<_>>();
+
+        let declared = std::iter::once( cfg.clone() )
+            .chain( version_cfgs.iter().map( |(_, version_cfg)| version_cfg.clone() ))
+            .collect::<Vec<_>>();
+        report::emit( format!( "rustc-check-cfg=cfg({})", declared.join( ", " )));
+        check_cfgs.extend( declared );
+
+        if for_docs.contains( &lib_name ) {
+            report::emit( format!( "rustc-cfg={}", cfg ));
+            cfgs.push( cfg );
+            let version_req = lib_info.specs.get( lib_name ).and_then( |spec| spec.version.as_ref() );
+            for (version, version_cfg) in version_cfgs {
+                if version_req.is_some_and( |req| req.ensures_at_least( version )) {
+                    report::emit( format!( "rustc-cfg={}", version_cfg ));
+                    cfgs.push( version_cfg );
+                }
+            }
+            continue;
+        }
+
+        let Some( lib ) = resolved.get( lib_name ).filter( |_| lib_names.contains( lib_name )) else { continue; };
+        report::emit( format!( "rustc-cfg={}", cfg ));
+        cfgs.push( cfg );
+        if let Some( found ) = &lib.version {
+            versions.push( format!( "{}={}", lib_name, found ));
+            for (version, version_cfg) in version_cfgs {
+                if version::compare( found, version ) != Ordering::Less {
+                    report::emit( format!( "rustc-cfg={}", version_cfg ));
+                    cfgs.push( version_cfg );
+                }
+            }
+        }
+    }
+
+    report::emit( format!( "cfgs={}", cfgs.join( " " )));
+    report::emit( format!( "check_cfgs={}", check_cfgs.join( " " )));
+    report::emit( format!( "versions={}", versions.join( " " )));
+}
+
+/// The cfg of a library, e.g. "clib_libxml_2_0" of "libxml-2.0".
+fn cfg_name( lib_name: &str ) -> String {
+    let name = lib_name
+        .chars()
+        .map( |c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect::<String>();
+    format!( "clib_{}", name )
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+    use crate::probe::Resolved;
+
+    fn generate_for( lib_names: &[&str] ) -> Result<String> {
+        let lib_info = LibInfo::new( Default::default(), Default::default(), None );
+        for lib_name in lib_names {
+            lib_info.resolved.borrow_mut().insert( lib_name.to_string(), Resolved::default() );
+        }
+        let lib_names = lib_names.iter().map( |lib_name| lib_name.to_string() ).collect::<Vec<_>>();
+        let out_path = std::env::temp_dir().join( format!( "clib-test-libs-{}-{}", std::process::id(), lib_names.join( "-" )));
+        fs::create_dir_all( &out_path )?;
+        let result = generate( &lib_info, &lib_names, &[], &out_path ).and_then( |_| Ok( fs::read_to_string( out_path.join( "libs.rs" ))? ));
+        fs::remove_dir_all( &out_path )?;
+        result
+    }
+
+    #[test]
+    fn rejects_colliding_const_names() {
+        let contents = generate_for( &[ "libxml-2.0", "zlib" ]).unwrap();
+        assert!( contents.contains( "pub const LIBXML_2_0: Lib" ), "{}", contents );
+        assert!( contents.contains( "pub const ALL: &[Lib] = &[LIBXML_2_0, ZLIB];" ), "{}", contents );
+
+        let err = generate_for( &[ "libxml-2.0", "libxml_2_0" ]).unwrap_err();
+        assert_eq!( err.to_string(), "libraries libxml-2.0 and libxml_2_0 have the same const name `LIBXML_2_0` in `clib::libs`" );
+
+        let err = generate_for( &[ "all" ]).unwrap_err();
+        assert!( err.to_string().starts_with( "library all has const name `ALL`" ), "{}", err );
+    }
+}
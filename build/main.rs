@@ -0,0 +1,240 @@
+mod bindings;
+mod cmake;
+mod compiler;
+mod configscript;
+mod libs;
+mod modules;
+mod overrides;
+mod pcfile;
+mod pkgconf;
+mod probe;
+mod report;
+mod search;
+mod spec;
+mod version;
+
+use anyhow::{
+    Context,
+    Result,
+    anyhow,
+};
+
+use probe::LibInfo;
+
+use report::Report;
+
+use spec::{BindgenOptions, Metadata, Origin, Spec};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    rc::Rc,
+    time::Instant,
+};
+
+const UTF8_PATH: &str = "path should be valid UTF-8 string.";
+
+fn generate_dummy() {
+    let out_path = PathBuf::from( env::var( "OUT_DIR" ).expect( "$OUT_DIR should exist." ));
+    File::create( out_path.join( "bindings.rs" )).expect( "an empty bindings.rs generated." );
+}
+
+fn main() -> Result<()> {
+    let mut specs = HashMap::<String,(Spec,Rc<Origin>)>::new(); // pkg name -> spec, and the package specifying it
+    let mut builds = BTreeMap::<String,Rc<Origin>>::new(); // builds -> the downstream package
+    let mut optional = HashMap::<String,bool>::new();  // builds -> whether every package asks for it optionally
+    let mut modules = BTreeMap::<bool,Vec<Rc<Origin>>>::new(); // `modules` -> the packages building libraries with it
+
+    // Sorted for a stable order, not depending on how cargo lists them.
+    let mut packages = inwelling::collect_downstream( inwelling::Opts::default() ).packages;
+    packages.sort_by( |a, b| (&a.name, &a.manifest).cmp( &(&b.name, &b.manifest) ));
+
+    for package in packages {
+        let metadata = Metadata::parse( &package )?;
+        for (pkg_name, spec) in metadata.specs {
+            match specs.get( &pkg_name ) {
+                Some(( existing, origin )) if *existing != spec => return Err( anyhow!(
+                    "conflicting specs of library {} in package `{}` ({}) and package `{}` ({})",
+                    pkg_name, origin.package, origin.manifest.display(), metadata.origin.package, metadata.origin.manifest.display() )),
+                Some(_) => (),
+                None => { specs.insert( pkg_name, (spec, metadata.origin.clone()) ); },
+            }
+        }
+        if !metadata.build.is_empty() {
+            modules.entry( metadata.modules ).or_default().push( metadata.origin.clone() );
+        }
+        for build in metadata.build {
+            *optional.entry( build.name.clone() ).or_insert( true ) &= build.optional;
+            builds.insert( build.name, metadata.origin.clone() );
+        }
+    }
+
+    // All downstream crates share one crate clib, in one mode or the other.
+    if let (Some( with ), Some( without )) = (modules.get( &true ), modules.get( &false )) {
+        let packages = |origins: &[Rc<Origin>]| origins
+            .iter()
+            .map( |origin| format!( "`{}` ({})", origin.package, origin.manifest.display() ))
+            .collect::<Vec<_>>()
+            .join( ", " );
+        return Err( anyhow!( "packages building libraries disagree on `modules`: set to true by {}, but not by {}",
+            packages( with ), packages( without )));
+    }
+    let use_modules = modules.contains_key( &true );
+
+    if builds.is_empty() {
+        generate_dummy();
+        libs::generate_empty( &PathBuf::from( env::var( "OUT_DIR" ).expect( "$OUT_DIR should exist." )))?;
+        return Ok(());
+    }
+
+    #[cfg( target_os = "freebsd" )]
+    env::set_var( "PKG_CONFIG_ALLOW_CROSS", "1" );
+
+    let specs = specs.into_iter().map( |(pkg_name, (spec, _))| (pkg_name, spec) ).collect();
+    let lib_info_all = LibInfo::new( specs, builds.keys().cloned().collect(), overrides::probe_order()? );
+
+    // A library failing to be probed fails the build if its spec is `required`, or
+    // if `CLIB_STRICT` is set and it is in `build` without being `optional`.
+    let strict = overrides::strict();
+    let is_required = |pkg_name: &String| {
+        lib_info_all.specs.get( pkg_name ).is_some_and( |spec| spec.required )
+            || (strict && optional.get( pkg_name ) == Some( &false ))
+    };
+
+    let docs_only = overrides::docs_only();
+    let mut downstream_files_for_docs_rs = Vec::<(&String,PathBuf)>::new();
+
+    builds.keys().filter( |pkg_name| !pkg_name.is_empty() ).for_each( |pkg_name| {
+        let for_docs_rs = lib_info_all.specs
+            .get( pkg_name )
+            .and_then( |spec| spec.for_docs_rs.as_deref() );
+
+        if docs_only {
+            // Pre-generated contents are preferred, not requiring libclang.
+            if let Some( for_docs_rs ) = for_docs_rs {
+                downstream_files_for_docs_rs.push(( pkg_name, for_docs_rs.to_owned() ));
+            } else if let Err( err ) = lib_info_all.resolve_for_docs( pkg_name ) {
+                report::emit( format!( "warning=[clib] no docs of library {}: {:#}", pkg_name, err ));
+            }
+        } else if lib_info_all.probe( pkg_name ).is_err() && !is_required( pkg_name ) {
+            for line in lib_info_all.failure_report( &(pkg_name.clone(), None) ) {
+                report::emit( format!( "warning=[clib] {}", line ));
+            }
+            if let Some( for_docs_rs ) = for_docs_rs {
+                downstream_files_for_docs_rs.push(( pkg_name, for_docs_rs.to_owned() ));
+            }
+        }
+    });
+
+    // Dependencies count too: a `required` one may fail while its dependents resolve without it.
+    // One failing only the version requirement of some dependent does not, if resolved for others.
+    let mut required_failures = lib_info_all.failed
+        .borrow()
+        .keys()
+        .filter( |(pkg_name, _)| is_required( pkg_name ) && !lib_info_all.resolved.borrow().contains_key( pkg_name ))
+        .cloned()
+        .collect::<Vec<_>>();
+    required_failures.sort();
+
+    let probed = builds
+        .keys()
+        .filter( |pkg_name| lib_info_all.resolved.borrow().contains_key( *pkg_name ))
+        .collect::<Vec<_>>();
+    for directive in lib_info_all.link_directives( &probed ) {
+        report::emit( directive );
+    }
+    let resolved = lib_info_all.resolved.borrow();
+
+    let closure = lib_info_all.closure( &probed );
+    let lib_names = closure
+        .iter()
+        .filter( |lib_name| !resolved[ *lib_name ].headers.is_empty() )
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let out_path = PathBuf::from( env::var( "OUT_DIR" ).expect( "$OUT_DIR should exist." ));
+
+    libs::generate( &lib_info_all, &closure, &lib_info_all.link_order( &probed ), &out_path )?;
+    let for_docs = downstream_files_for_docs_rs
+        .iter()
+        .filter( |_| docs_only )
+        .map( |(pkg_name, _)| *pkg_name )
+        .collect::<Vec<_>>();
+    libs::emit_cfgs( &lib_info_all, &closure, &for_docs );
+
+    let clang_args = lib_info_all.clang_args_of( &closure );
+    let mut report = Report{ builds: &builds, lib_info: &lib_info_all, clang_args: &clang_args, bindgen: None };
+    // Written before bindgen runs too, for the report to survive its failure.
+    report.write( &out_path )?;
+
+    if !required_failures.is_empty() {
+        let reports = required_failures
+            .iter()
+            .flat_map( |key| lib_info_all.failure_report( key ))
+            .collect::<Vec<_>>();
+        let mut names = required_failures.into_iter().map( |(pkg_name, _)| pkg_name ).collect::<Vec<_>>();
+        names.dedup();
+        return Err( anyhow!( "required libraries failed to be probed: {}\n{}",
+            names.join( ", " ), reports.join( "\n" )));
+    }
+    let bindgen_start = Instant::now();
+
+    if use_modules {
+        let docs_rs = downstream_files_for_docs_rs
+            .iter()
+            .map( |(pkg_name, path)| Ok(( (*pkg_name).clone(), fs::read_to_string( path )
+                .with_context( || format!( "contents for generating docs on docs.rs should be read from {:?}", path ))? )))
+            .collect::<Result<HashMap<_,_>>>()?;
+        let builds = builds.keys().filter( |pkg_name| !pkg_name.is_empty() ).collect::<Vec<_>>();
+        modules::generate( &lib_info_all, &builds, &docs_rs, &out_path )?;
+        report.bindgen = Some( bindgen_start.elapsed() );
+    } else if !lib_names.is_empty() {
+        // All libraries share one bindgen run in the root namespace, those without spec too.
+        let no_options = BindgenOptions::default();
+        let options = BindgenOptions::merge( lib_names
+            .iter()
+            .map( |lib_name| (lib_name, lib_info_all.specs.get( lib_name ).map_or( &no_options, |spec| &spec.bindgen )) ))?;
+
+        let mut builder = bindings::builder( &options );
+        for lib_name in &lib_names {
+            if let Some( spec ) = lib_info_all.specs.get( lib_name ) {
+                let lib = &resolved[ lib_name ];
+                builder = bindings::restrict( builder, &lib.include_dir, &lib.headers, &spec.include_root, &spec.bindgen );
+            }
+        }
+
+        // Headers of `header-dependencies` are not parsed on their own.
+        let linked = lib_info_all.link_order( &probed );
+        for lib_name in lib_names.iter().filter( |lib_name| linked.contains( lib_name )) {
+            for header in &resolved[ lib_name ].headers {
+                builder = builder.header( header );
+            }
+        }
+        builder = builder.clang_args( clang_args.iter() );
+
+        let bindings = builder.generate().expect( "bindgen builder constructed." );
+        bindings.write_to_file( out_path.join( "bindings.rs" )).expect( "bindings.rs generated." );
+        report.bindgen = Some( bindgen_start.elapsed() );
+    } else {
+        generate_dummy();
+    }
+
+    // Without modules, `for-docs-rs` contents follow the bindings of the other libraries.
+    if !use_modules && !downstream_files_for_docs_rs.is_empty() {
+        let mut out_file = OpenOptions::new()
+            .append( true )
+            .open( out_path.join( "bindings.rs" ))
+            .unwrap_or_else( |_| panic!( "{:?} should be opened for add contents for docs.rs.", out_path ));
+        for (_, path) in &downstream_files_for_docs_rs {
+            let contents = fs::read_to_string( path )
+                .unwrap_or_else( |_| panic!( "contents for generating docs on docs.rs should be read from {:?}", path ));
+            writeln!( &mut out_file, "{}", contents )
+                .unwrap_or_else( |_| panic!( "Some contents for generating docs on docs.rs should be appended to {:?}.", out_path ));
+        }
+    }
+
+    report.write( &out_path )
+}
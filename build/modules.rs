@@ -0,0 +1,127 @@
+//! Per-library modules, enabled by `modules = true` in any downstream package.
+//!
+//! Every built library gets its own module, e.g. `clib::tk86`, and so does each
+//! of its dependencies providing headers. Items declared in a dependency's files
+//! are blocklisted from the dependent's bindings and imported from the
+//! dependency's module instead.
+
+use crate::{bindings, probe::LibInfo, search};
+
+use anyhow::Result;
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    path::Path,
+    rc::Rc,
+};
+
+/// Records the files included while generating bindings of one library.
+#[derive( Debug )]
+struct IncludedFiles( Rc<RefCell<Vec<String>>> );
+
+impl bindgen::callbacks::ParseCallbacks for IncludedFiles {
+    fn include_file( &self, filename: &str ) {
+        self.0.borrow_mut().push( filename.to_owned() );
+    }
+}
+
+/// Generates one `{module}.rs` per library in `out_path`, and `bindings.rs`
+/// declaring these modules and re-exporting the built ones from crate root.
+///
+/// Libraries failed to probe, or built for docs only, are listed in `docs_rs` with
+/// their `for-docs-rs` contents, if any, which become the contents of their modules.
+pub fn generate( lib_info: &LibInfo, builds: &[&String], docs_rs: &HashMap<String,String>, out_path: &Path ) -> Result<()> {
+    let resolved = lib_info.resolved.borrow();
+
+    let mut order = lib_info.closure( builds );
+    order.retain( |lib| !resolved[ lib ].headers.is_empty() );
+    order.extend( builds.iter().filter( |lib| docs_rs.contains_key( **lib )).map( |lib| lib.to_string() ));
+
+    let mut files_of = HashMap::<&str,Vec<String>>::new();
+    let mut declarations = String::new();
+
+    for lib in &order {
+        let module = module_name( lib );
+        let file_name = format!( "{}.rs", module );
+
+        if let Some( contents ) = docs_rs.get( lib ) {
+            fs::write( out_path.join( &file_name ), contents )?;
+        } else {
+            let headers = &resolved[ lib ].headers;
+            let included = Rc::new( RefCell::new( headers.clone() ));
+
+            let spec = lib_info.specs.get( lib );
+            let options = spec.map( |spec| spec.bindgen.clone() ).unwrap_or_default();
+            let include_root = spec
+                .map( |spec| spec.include_root.clone() )
+                .unwrap_or_else( || search::default_dirs( search::INCLUDE_ROOT ));
+            let mut builder = bindings::builder( &options )
+                .parse_callbacks( Box::new( IncludedFiles( included.clone() )))
+            ;
+            builder = bindings::restrict( builder, &resolved[ lib ].include_dir, headers, &include_root, &options );
+
+            for header in headers {
+                builder = builder.header( header );
+            }
+
+            // The library itself comes last in its closure, but its own clang args go first.
+            let mut own_and_deps = lib_info.closure( &[lib] );
+            own_and_deps.rotate_right( 1 );
+            builder = builder.clang_args( lib_info.clang_args_of( &own_and_deps ));
+            for dependency in order.iter().filter( |dep| own_and_deps[ 1.. ].contains( *dep )) {
+                builder = builder.raw_line( format!( "#[allow( unused_imports )] use super::{}::*;", module_name( dependency )));
+                for file in files_of.get( dependency.as_str() ).into_iter().flatten() {
+                    builder = builder.blocklist_file( bindings::escape_regex( file ));
+                }
+            }
+
+            builder
+                .generate()
+                .expect( "bindgen builder constructed." )
+                .write_to_file( out_path.join( &file_name ))
+                .expect( "module generated." );
+
+            let mut included = included.take();
+            included.sort();
+            included.dedup();
+            files_of.insert( lib, included );
+        }
+
+        writeln!( declarations, "pub mod {} {{ include!( concat!( env!( \"OUT_DIR\" ), \"/{}\" )); }}", module, file_name )?;
+    }
+
+    for pkg_name in builds {
+        if order.contains( pkg_name ) {
+            writeln!( declarations, "pub use {}::*;", module_name( pkg_name ))?;
+        }
+    }
+
+    fs::write( out_path.join( "bindings.rs" ), declarations )?;
+    Ok(())
+}
+
+/// Converts a library name into a valid Rust identifier, e.g. "libxml-2.0" to "libxml_2_0".
+pub fn module_name( lib: &str ) -> String {
+    let mut name = lib
+        .chars()
+        .map( |c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if name.is_empty() || name.starts_with( |c: char| c.is_ascii_digit() ) {
+        name.insert( 0, '_' );
+    }
+    if is_keyword( &name ) {
+        name.push( '_' );
+    }
+    name
+}
+
+fn is_keyword( name: &str ) -> bool {
+    matches!( name,
+        "as" | "async" | "await" | "box" | "break" | "const" | "continue" | "crate" | "dyn" | "else" |
+        "enum" | "extern" | "false" | "fn" | "for" | "gen" | "if" | "impl" | "in" | "let" | "loop" |
+        "match" | "mod" | "move" | "mut" | "pub" | "ref" | "return" | "self" | "static" | "struct" |
+        "super" | "trait" | "true" | "try" | "type" | "unsafe" | "use" | "where" | "while" | "yield" )
+}
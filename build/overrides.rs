@@ -0,0 +1,106 @@
+//! Per-library overrides from environment variables, e.g. `CLIB_TK86_PREFIX`.
+
+use crate::{
+    report,
+    spec::{self, PROBE_STRATEGIES},
+};
+
+use anyhow::{Result, anyhow};
+
+use std::{
+    env,
+    path::PathBuf,
+};
+
+/// Serializes the tests setting environment variables, e.g. `PKG_CONFIG_PATH`.
+#[cfg( test )]
+pub static TEST_ENV: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Suffixes of the environment variables read for each library.
+const SUFFIXES: &[&str] = &[ "INCLUDE_DIR", "LIB_DIR", "PREFIX", "NO_PKG_CONFIG", "STATIC" ];
+
+/// What `CLIB_<NAME>_*` environment variables say about library `<name>`.
+#[derive( Debug, Default )]
+pub struct Overrides {
+    pub include_dir   : Option<PathBuf>,
+    pub lib_dir       : Option<PathBuf>,
+    pub prefix        : Option<PathBuf>,
+    pub no_pkg_config : bool,
+    pub statik        : bool,
+}
+
+impl Overrides {
+    /// Reads the overrides of the library, telling cargo to rerun the build script
+    /// if any of them changes.
+    pub fn of( pkg_name: &str ) -> Self {
+        let var_name = |suffix: &str| format!( "CLIB_{}_{}", env_name( pkg_name ), suffix );
+        for suffix in SUFFIXES {
+            report::emit( format!( "rerun-if-env-changed={}", var_name( suffix )));
+        }
+
+        let path = |suffix: &str| env::var_os( var_name( suffix ))
+            .filter( |value| !value.is_empty() )
+            .map( PathBuf::from );
+        let flag = |suffix: &str| env::var( var_name( suffix ))
+            .is_ok_and( |value| !value.is_empty() && value != "0" );
+
+        Overrides {
+            include_dir   : path( "INCLUDE_DIR" ),
+            lib_dir       : path( "LIB_DIR" ),
+            prefix        : path( "PREFIX" ),
+            no_pkg_config : flag( "NO_PKG_CONFIG" ),
+            statik        : flag( "STATIC" ),
+        }
+    }
+
+    /// Whether the library is located by the environment variables, rather than probed:
+    /// its libs are, while its include dir alone does not tell where to link from.
+    pub fn locates( &self ) -> bool {
+        self.lib_dir.is_some() || self.prefix.is_some()
+    }
+}
+
+/// The library name as part of environment variable names, e.g. "LIBXML_2_0" for "libxml-2.0".
+pub fn env_name( pkg_name: &str ) -> String {
+    pkg_name
+        .chars()
+        .map( |c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+/// Whether `CLIB_STRICT` is set, failing the build if any library in `build`, other
+/// than `optional` ones, fails to be probed.
+pub fn strict() -> bool {
+    report::emit( "rerun-if-env-changed=CLIB_STRICT".to_owned() );
+    env::var( "CLIB_STRICT" ).is_ok_and( |value| !value.is_empty() && value != "0" )
+}
+
+/// Whether docs are built only, by docs.rs setting `DOCS_RS` or by `CLIB_DOCS_ONLY`,
+/// in which case libraries are neither probed nor linked.
+pub fn docs_only() -> bool {
+    const VARS: [&str; 2] = [ "DOCS_RS", "CLIB_DOCS_ONLY" ];
+    // Both are watched, whichever is set.
+    for var in VARS {
+        report::emit( format!( "rerun-if-env-changed={}", var ));
+    }
+    VARS.into_iter().any( |var| env::var( var ).is_ok_and( |value| !value.is_empty() && value != "0" ))
+}
+
+/// The order of probe strategies in `CLIB_PROBE_ORDER`, e.g. "vendored,pkg-config",
+/// taking precedence over the `probe` of every spec.
+pub fn probe_order() -> Result<Option<Vec<String>>> {
+    report::emit( "rerun-if-env-changed=CLIB_PROBE_ORDER".to_owned() );
+
+    let Ok( value ) = env::var( "CLIB_PROBE_ORDER" ) else { return Ok( None ); };
+    let order = value
+        .split( |c: char| c == ',' || c.is_whitespace() )
+        .filter( |strategy| !strategy.is_empty() )
+        .map( ToOwned::to_owned )
+        .collect::<Vec<_>>();
+    for strategy in &order {
+        if !PROBE_STRATEGIES.contains( &strategy.as_str() ) {
+            return Err( anyhow!( "CLIB_PROBE_ORDER: {}", spec::unknown( "probe strategy", strategy, PROBE_STRATEGIES )));
+        }
+    }
+    Ok( Some( order ).filter( |order| !order.is_empty() ))
+}
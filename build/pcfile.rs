@@ -0,0 +1,447 @@
+//! A reader of .pc files in pure Rust, probing libraries when the `pkg-config`
+//! executable is not available.
+
+use crate::{
+    UTF8_PATH,
+    pkgconf,
+    search,
+    version::VersionReq,
+};
+
+use anyhow::{Context, Result, anyhow};
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Directories searched for .pc files if `PKG_CONFIG_LIBDIR` is not set.
+const DEFAULT_DIRS: &[&str] = &[
+    "/usr/local/lib/<triple>/pkgconfig",
+    "/usr/local/lib64/pkgconfig",
+    "/usr/local/lib/pkgconfig",
+    "/usr/local/share/pkgconfig",
+    "/usr/lib/<triple>/pkgconfig",
+    "/usr/lib64/pkgconfig",
+    "/usr/lib/pkgconfig",
+    "/usr/share/pkgconfig",
+];
+
+/// Variables and fields of one .pc file.
+#[derive( Debug )]
+struct PcFile {
+    path      : PathBuf,
+    variables : HashMap<String,String>,
+    fields    : HashMap<String,String>,
+}
+
+/// A library found in .pc files, including what its `Requires` contribute.
+#[derive( Debug )]
+pub struct Package {
+    pub path             : PathBuf, // the .pc file
+    pub version          : String,
+    pub includedir       : String,
+    pub cflags           : Vec<String>,
+    pub libs             : Vec<String>,
+    pub requires         : Vec<(String,Option<VersionReq>)>,
+    pub requires_private : Vec<(String,Option<VersionReq>)>,
+}
+
+impl PcFile {
+    /// Locates `pc_name`.pc in `PKG_CONFIG_PATH`, then in `PKG_CONFIG_LIBDIR` or the
+    /// default directories.
+    fn find( pc_name: &str ) -> Result<PcFile> {
+        let file_name = format!( "{}.pc", pc_name );
+        let path = search_dirs()
+            .into_iter()
+            .map( |dir| dir.join( &file_name ))
+            .find( |path| path.is_file() )
+            .with_context( || format!( "{} not found in {:?}", file_name, search_dirs() ))?;
+        let text = fs::read_to_string( &path ).with_context( || format!( "failed to read {:?}", path ))?;
+        PcFile::parse( path, &text )
+    }
+
+    fn parse( path: PathBuf, text: &str ) -> Result<PcFile> {
+        let dir = path.parent().expect( "a .pc file should be in a directory." );
+        let mut pc_file = PcFile {
+            variables : HashMap::from([ ( "pcfiledir".to_owned(), dir.to_str().context( UTF8_PATH )?.to_owned() )]),
+            fields    : HashMap::new(),
+            path      : path.clone(),
+        };
+
+        let text = text.replace( "\\\r\n", "" ).replace( "\\\n", "" );
+        for line in text.lines() {
+            let line = strip_comment( line );
+            let line = line.trim();
+            let Some( sep ) = line.find( ['=', ':'] ) else { continue; };
+            let (key, value) = (line[ ..sep ].trim(), line[ sep+1.. ].trim());
+            if key.is_empty() || !key.chars().all( |c| c.is_ascii_alphanumeric() || "_.-".contains( c )) {
+                continue;
+            }
+            let value = pc_file.expand( value )?;
+            if line.as_bytes()[ sep ] == b'=' {
+                pc_file.variables.insert( key.to_owned(), value );
+            } else {
+                pc_file.fields.insert( key.to_owned(), value );
+            }
+        }
+        Ok( pc_file )
+    }
+
+    /// Interpolates `${name}` with variables defined so far, and `$$` with `$`.
+    fn expand( &self, value: &str ) -> Result<String> {
+        let mut expanded = String::with_capacity( value.len() );
+        let mut rest = value;
+        while let Some( dollar ) = rest.find( '$' ) {
+            expanded.push_str( &rest[ ..dollar ]);
+            rest = &rest[ dollar+1.. ];
+            if let Some( after ) = rest.strip_prefix( '$' ) {
+                expanded.push( '$' );
+                rest = after;
+            } else if let Some( after ) = rest.strip_prefix( '{' ) {
+                let end = after.find( '}' ).with_context( || format!( "unterminated variable in {:?}", self.path ))?;
+                let name = &after[ ..end ];
+                let value = self.variables
+                    .get( name )
+                    .with_context( || format!( "undefined variable `{}` in {:?}", name, self.path ))?;
+                expanded.push_str( value );
+                rest = &after[ end+1.. ];
+            } else {
+                expanded.push( '$' );
+            }
+        }
+        expanded.push_str( rest );
+        Ok( expanded )
+    }
+
+    fn field( &self, name: &str ) -> &str {
+        self.fields.get( name ).map( String::as_str ).unwrap_or_default()
+    }
+}
+
+/// Directories of .pc files in the order of searching.
+pub fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = env::var_os( "PKG_CONFIG_PATH" )
+        .map( |paths| env::split_paths( &paths ).collect::<Vec<_>>() )
+        .unwrap_or_default();
+    match env::var_os( "PKG_CONFIG_LIBDIR" ) {
+        Some( paths ) => dirs.extend( env::split_paths( &paths )),
+        None => dirs.extend( DEFAULT_DIRS
+            .iter()
+            .filter_map( |dir| search::expand_triple( dir ))
+            .map( PathBuf::from )),
+    }
+    dirs.retain( |dir| !dir.as_os_str().is_empty() );
+    dirs
+}
+
+/// Probes `pc_name` and its `Requires`, recursively, checking their versions.
+/// `Libs.private` and `Requires.private` are linked too, if `statik`.
+pub fn probe( pc_name: &str, version_req: Option<&VersionReq>, statik: bool ) -> Result<Package> {
+    let pc_file = PcFile::find( pc_name )?;
+    let version = pc_file.field( "Version" ).to_owned();
+    if let Some( version_req ) = version_req {
+        version_req.check( pc_name, &version, &pc_file.path.display().to_string() )?;
+    }
+
+    let includedir = pc_file.variables.get( "includedir" ).cloned().unwrap_or_default();
+
+    let mut visited = HashSet::from([ pc_name.to_owned() ]);
+    let (mut cflags, mut libs) = (Vec::new(), Vec::new());
+    collect_flags( &pc_file, statik, &mut visited, &mut cflags, &mut libs )?;
+
+    let cflags = dedup_first( cflags.into_iter().map( |flag| sysroot_flag( &flag )));
+    let (lib_dirs, others) = libs
+        .into_iter()
+        .map( |flag| sysroot_flag( &flag ))
+        .partition::<Vec<_>,_>( |flag| flag.starts_with( "-L" ));
+    // Each library is linked after all the libraries needing it.
+    let mut others = dedup_first( others.into_iter().rev() );
+    others.reverse();
+    let mut libs = dedup_first( lib_dirs );
+    libs.extend( others );
+
+    Ok( Package {
+        path             : pc_file.path.clone(),
+        version          ,
+        includedir       : with_sysroot( &includedir ),
+        cflags           ,
+        libs             ,
+        requires         : requirements( pc_file.field( "Requires" ))?,
+        requires_private : requirements( pc_file.field( "Requires.private" ))?,
+    })
+}
+
+fn collect_flags( pc_file: &PcFile, statik: bool, visited: &mut HashSet<String>, cflags: &mut Vec<String>, libs: &mut Vec<String> ) -> Result<()> {
+    cflags.extend( pkgconf::split_words( pc_file.field( "Cflags" )));
+    libs.extend( pkgconf::split_words( pc_file.field( "Libs" )));
+    if statik {
+        libs.extend( pkgconf::split_words( pc_file.field( "Libs.private" )));
+    }
+
+    // Headers of private dependencies may be included by public headers.
+    let requires = requirements( pc_file.field( "Requires" ))?
+        .into_iter()
+        .map( |required| (required, true) )
+        .chain( requirements( pc_file.field( "Requires.private" ))?
+            .into_iter()
+            .map( |required| (required, statik) ));
+
+    for ((name, version_req), linked) in requires {
+        if visited.insert( name.clone() ) {
+            let required = PcFile::find( &name )
+                .with_context( || format!( "{:?} requires {}", pc_file.path, name ))?;
+            if let Some( version_req ) = version_req {
+                version_req.check( &name, required.field( "Version" ), &required.path.display().to_string() )?;
+            }
+            let mut required_libs = Vec::new();
+            collect_flags( &required, statik, visited, cflags, &mut required_libs )?;
+            if linked {
+                libs.extend( required_libs );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses `Requires`, e.g. "tcl >= 8.6, zlib", into names and version requirements.
+pub fn requirements( requires: &str ) -> Result<Vec<(String,Option<VersionReq>)>> {
+    const OPS: &[&str] = &[ ">=", "<=", "!=", "=", ">", "<" ];
+
+    let mut words = requires
+        .split( |c: char| c == ',' || c.is_whitespace() )
+        .flat_map( split_operators )
+        .peekable();
+    let mut requirements = Vec::new();
+
+    while let Some( name ) = words.next() {
+        let mut version_req = None;
+        if let Some( op ) = words.next_if( |word| OPS.contains( word )) {
+            let version = words.next().ok_or_else( || anyhow!( "version missing after `{} {}`", name, op ))?;
+            // "!=" is not a range, and rarely used.
+            if op != "!=" {
+                version_req = Some( VersionReq::parse( &format!( "{}{}", op, version ))? );
+            }
+        }
+        requirements.push(( name.to_owned(), version_req ));
+    }
+    Ok( requirements )
+}
+
+/// Splits operators from names and versions written without spaces, e.g. "tcl>=8.6".
+fn split_operators( word: &str ) -> impl Iterator<Item=&str> {
+    let is_op = |c: char| "<>=!".contains( c );
+    let mut rest = word;
+    std::iter::from_fn( move || {
+        let first = rest.chars().next()?;
+        let (run, after) = rest.split_at( rest.find( |c: char| is_op( c ) != is_op( first )).unwrap_or( rest.len() ));
+        rest = after;
+        Some( run )
+    })
+}
+
+fn strip_comment( line: &str ) -> String {
+    let mut stripped = String::with_capacity( line.len() );
+    let mut chars = line.chars();
+    while let Some( c ) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some( '#' ) => stripped.push( '#' ),
+                Some( c ) => { stripped.push( '\\' ); stripped.push( c ); },
+                None => stripped.push( '\\' ),
+            },
+            '#' => break,
+            c => stripped.push( c ),
+        }
+    }
+    stripped
+}
+
+/// Prepends `PKG_CONFIG_SYSROOT_DIR` to the path.
+fn with_sysroot( path: &str ) -> String {
+    match env::var( "PKG_CONFIG_SYSROOT_DIR" ) {
+        Ok( sysroot ) if !sysroot.is_empty() && path.starts_with( '/' ) && !path.starts_with( &sysroot ) => format!( "{}{}", sysroot, path ),
+        _ => path.to_owned(),
+    }
+}
+
+/// Prepends `PKG_CONFIG_SYSROOT_DIR` to the path of `-I` and `-L` flags.
+fn sysroot_flag( flag: &str ) -> String {
+    match flag.get( ..2 ) {
+        Some( prefix @ ("-I" | "-L") ) => format!( "{}{}", prefix, with_sysroot( &flag[ 2.. ])),
+        _ => flag.to_owned(),
+    }
+}
+
+fn dedup_first( flags: impl IntoIterator<Item=String> ) -> Vec<String> {
+    let mut seen = HashSet::new();
+    flags.into_iter().filter( |flag| seen.insert( flag.clone() )).collect()
+}
+
+/// Cargo directives for linking the flags in `Libs` of `pc_name`.pc, like
+/// `pkgconf::link_directives()`.
+pub fn link_directives( pc_name: &str, libs: &[String], statik: bool ) -> Vec<String> {
+    let prefers_static = pkgconf::prefers_static( pc_name, statik );
+    let lib_dirs = libs.iter().filter_map( |flag| flag.strip_prefix( "-L" )).collect::<Vec<_>>();
+    let mut directives = lib_dirs
+        .iter()
+        .map( |dir| format!( "rustc-link-search=native={}", dir ))
+        .collect::<Vec<_>>();
+
+    let mut flags = libs.iter();
+    while let Some( flag ) = flags.next() {
+        if let Some( lib ) = flag.strip_prefix( "-l" ) {
+            let has_archive = lib_dirs
+                .iter()
+                .any( |dir| Path::new( dir ).join( format!( "lib{}.a", lib )).exists() );
+            if prefers_static && has_archive {
+                directives.push( format!( "rustc-link-lib=static={}", lib ));
+            } else {
+                directives.push( format!( "rustc-link-lib={}", lib ));
+            }
+        } else if let Some( dir ) = flag.strip_prefix( "-F" ) {
+            directives.push( format!( "rustc-link-search=framework={}", dir ));
+        } else if flag == "-framework" {
+            if let Some( framework ) = flags.next() {
+                directives.push( format!( "rustc-link-lib=framework={}", framework ));
+            }
+        } else if flag.starts_with( "-Wl," ) || !flag.starts_with( '-' ) {
+            directives.push( format!( "rustc-link-arg={}", flag ));
+        }
+    }
+    directives
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+    use crate::overrides::TEST_ENV;
+
+    const ZLIB_PC: &str = "\
+prefix=/opt/zlib
+libdir=${prefix}/lib
+includedir=${prefix}/include
+
+Name: zlib
+Version: 1.2.13
+Libs: -L${libdir} -lz
+Cflags: -I${includedir}
+";
+
+    const LIBPNG_PC: &str = "\
+prefix=/opt/png
+libdir=${prefix}/lib
+includedir=${prefix}/include/libpng16
+
+Name: libpng
+Version: 1.6.40
+Requires.private: zlib
+Libs: -L${libdir} -lpng16
+Libs.private: -lm
+Cflags: -I${includedir}
+";
+
+    const MYLIB_PC: &str = "\
+prefix=/opt/mylib
+Name: mylib
+Version: 0.1
+Requires: libpng >= 1.6
+Libs: -L${prefix}/lib -lmylib
+Cflags: -I${prefix}/include
+";
+
+    /// Writes the .pc files in a fresh directory searched alone by pkg-config.
+    fn pc_dir( name: &str, pc_files: &[(&str, &str)] ) -> PathBuf {
+        let dir = env::temp_dir().join( format!( "clib-test-{}-{}", name, std::process::id() ));
+        fs::create_dir_all( &dir ).unwrap();
+        for (pc_name, text) in pc_files {
+            fs::write( dir.join( format!( "{}.pc", pc_name )), text ).unwrap();
+        }
+        env::set_var( "PKG_CONFIG_PATH", &dir );
+        env::set_var( "PKG_CONFIG_LIBDIR", "" );
+        env::remove_var( "PKG_CONFIG_SYSROOT_DIR" );
+        dir
+    }
+
+    #[test]
+    fn expands_variables() {
+        let text = "\
+prefix=/opt/foo # the install prefix
+exec_prefix=${prefix}
+libdir=${exec_prefix}/lib
+price=$$5
+Cflags: -I${prefix}/include \\
+    -DFOO=\\#1 -DDIR=${pcfiledir}
+Libs: -L${libdir} -lfoo
+";
+        let pc_file = PcFile::parse( PathBuf::from( "/pc/foo.pc" ), text ).unwrap();
+        assert_eq!( pc_file.variables[ "prefix" ], "/opt/foo" );
+        assert_eq!( pc_file.variables[ "libdir" ], "/opt/foo/lib" );
+        assert_eq!( pc_file.variables[ "price" ], "$5" );
+        assert_eq!( pkgconf::split_words( pc_file.field( "Cflags" )), [ "-I/opt/foo/include", "-DFOO=#1", "-DDIR=/pc" ]);
+        assert_eq!( pc_file.field( "Libs" ), "-L/opt/foo/lib -lfoo" );
+
+        assert!( PcFile::parse( PathBuf::from( "/pc/foo.pc" ), "Libs: -L${libdir}" ).is_err() );
+        assert!( PcFile::parse( PathBuf::from( "/pc/foo.pc" ), "libdir=${prefix" ).is_err() );
+    }
+
+    #[test]
+    fn parses_requirements() {
+        let requires = requirements( "tcl >= 8.6, zlib,libpng!=1.6.0 x11>=1.8" ).unwrap();
+        let requires = requires
+            .iter()
+            .map( |(name, version_req)| (name.as_str(), version_req.as_ref().map( ToString::to_string )))
+            .collect::<Vec<_>>();
+        assert_eq!( requires, [
+            ("tcl", Some( ">=8.6".to_owned() )),
+            ("zlib", None),
+            ("libpng", None),
+            ("x11", Some( ">=1.8".to_owned() )),
+        ]);
+        assert!( requirements( "tcl >=" ).is_err() );
+    }
+
+    #[test]
+    fn probes_requires_recursively() {
+        let _env = TEST_ENV.lock().unwrap();
+        let dir = pc_dir( "requires", &[ ("zlib", ZLIB_PC), ("libpng", LIBPNG_PC), ("mylib", MYLIB_PC) ]);
+
+        // `Requires.private` contribute cflags only, unless linked statically.
+        let package = probe( "mylib", None, false ).unwrap();
+        assert_eq!( package.version, "0.1" );
+        assert_eq!( package.cflags, [ "-I/opt/mylib/include", "-I/opt/png/include/libpng16", "-I/opt/zlib/include" ]);
+        assert_eq!( package.libs, [ "-L/opt/mylib/lib", "-L/opt/png/lib", "-lmylib", "-lpng16" ]);
+        assert_eq!( package.requires[0].0, "libpng" );
+        assert!( package.requires_private.is_empty() );
+
+        let package = probe( "mylib", None, true ).unwrap();
+        assert_eq!( package.libs, [ "-L/opt/mylib/lib", "-L/opt/png/lib", "-L/opt/zlib/lib", "-lmylib", "-lpng16", "-lm", "-lz" ]);
+
+        assert!( probe( "mylib", Some( &VersionReq::parse( ">=1.0" ).unwrap() ), false ).is_err() );
+
+        fs::write( dir.join( "mylib.pc" ), MYLIB_PC.replace( ">= 1.6", ">= 1.7" )).unwrap();
+        assert!( probe( "mylib", None, false ).is_err() );
+        fs::remove_file( dir.join( "zlib.pc" )).unwrap();
+        assert!( probe( "libpng", None, false ).is_err() );
+
+        fs::remove_dir_all( dir ).unwrap();
+    }
+
+    #[test]
+    fn prepends_sysroot() {
+        let _env = TEST_ENV.lock().unwrap();
+        let dir = pc_dir( "sysroot", &[ ("zlib", ZLIB_PC) ]);
+        env::set_var( "PKG_CONFIG_SYSROOT_DIR", "/sysroot" );
+
+        let package = probe( "zlib", None, false );
+        env::remove_var( "PKG_CONFIG_SYSROOT_DIR" );
+        let package = package.unwrap();
+        assert_eq!( package.includedir, "/sysroot/opt/zlib/include" );
+        assert_eq!( package.cflags, [ "-I/sysroot/opt/zlib/include" ]);
+        assert_eq!( package.libs, [ "-L/sysroot/opt/zlib/lib", "-lz" ]);
+
+        fs::remove_dir_all( dir ).unwrap();
+    }
+}
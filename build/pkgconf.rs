@@ -0,0 +1,193 @@
+//! Queries of the `pkg-config` executable beyond what crate pkg-config provides.
+
+use crate::{pcfile, version::VersionReq};
+
+use anyhow::{Context, Result, anyhow};
+
+use std::{
+    env,
+    process::Command,
+};
+
+/// Flags which may be followed by their argument as the next word.
+const FLAGS_WITH_ARG: &[&str] = &[
+    "-I", "-D", "-U", "-F", "-isystem", "-iquote", "-idirafter", "-include", "-imacros",
+];
+
+fn command() -> Command {
+    Command::new( env::var( "PKG_CONFIG" ).unwrap_or_else( |_| "pkg-config".to_owned() ))
+}
+
+/// Runs pkg-config with `args`, failing if it does.
+fn query( args: &[&str] ) -> Result<String> {
+    let output = command().args( args ).output().context( "failed to run pkg-config" )?;
+    if !output.status.success() {
+        return Err( anyhow!( "`pkg-config {}` failed with {}: {}",
+            args.join( " " ), output.status, String::from_utf8_lossy( &output.stderr ).trim() ));
+    }
+    Ok( String::from_utf8( output.stdout )? )
+}
+
+/// Queries a variable defined in the .pc file, e.g. "includedir".
+pub fn variable( pc_name: &str, name: &str ) -> Result<String> {
+    Ok( query( &[ pc_name, "--variable", name ])?.trim_end().to_owned() )
+}
+
+/// Queries the version of the .pc file, regardless of any version requirement.
+pub fn modversion( pc_name: &str ) -> Result<String> {
+    let output = command().args( [ "--modversion", pc_name ]).output()?;
+    if !output.status.success() {
+        return Err( anyhow!( "{}.pc not found", pc_name ));
+    }
+    Ok( std::str::from_utf8( output.stdout.as_slice() )?.trim().to_owned() )
+}
+
+/// Queries `Requires`, or `Requires.private` if `private`, of the .pc file.
+pub fn requires( pc_name: &str, private: bool ) -> Result<Vec<(String,Option<VersionReq>)>> {
+    let option = if private { "--print-requires-private" } else { "--print-requires" };
+    pcfile::requirements( &query( &[ option, pc_name ])? )
+}
+
+/// Queries the Cflags of the .pc file, keeping the ones affecting how headers are
+/// parsed, e.g. include paths, defines and `-pthread`, as clang args.
+pub fn cflags( pc_name: &str ) -> Result<Vec<String>> {
+    Ok( clang_args( split_words( &query( &[ "--cflags", pc_name ])? )))
+}
+
+/// Picks clang args from the words of compiler flags, joining each flag with its argument.
+pub fn clang_args( words: Vec<String> ) -> Vec<String> {
+    let mut words = words.into_iter();
+    let mut args = Vec::new();
+
+    while let Some( word ) = words.next() {
+        if FLAGS_WITH_ARG.contains( &word.as_str() ) {
+            if let Some( arg ) = words.next() {
+                args.push( format!( "{}{}", word, arg ));
+            }
+        } else if FLAGS_WITH_ARG.iter().any( |flag| word.starts_with( flag ))
+            || word == "-pthread"
+            || word.starts_with( "-std=" )
+            || word.starts_with( "-f" )
+            || word.starts_with( "-m" )
+        {
+            args.push( word );
+        }
+    }
+    args
+}
+
+/// Splits a command line into words, honoring quotes and backslash escapes.
+pub fn split_words( line: &str ) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quote = None::<char>;
+    let mut chars = line.chars();
+
+    while let Some( c ) = chars.next() {
+        match (c, quote) {
+            ('\\', Some('\'')) => word.get_or_insert_with( String::new ).push( c ),
+            ('\\', _) => if let Some( escaped ) = chars.next() {
+                word.get_or_insert_with( String::new ).push( escaped );
+            },
+            (c, Some( q )) if c == q => quote = None,
+            (c, Some(_)) => word.get_or_insert_with( String::new ).push( c ),
+            ('"' | '\'', None) => {
+                quote = Some( c );
+                word.get_or_insert_with( String::new );
+            },
+            (c, None) if c.is_whitespace() => words.extend( word.take() ),
+            (c, None) => word.get_or_insert_with( String::new ).push( c ),
+        }
+    }
+    words.extend( word );
+    words
+}
+
+/// Whether to link static archives of `pc_name`.pc, if `statik`, `<PC_NAME>_STATIC`
+/// or `PKG_CONFIG_ALL_STATIC` is set, unless `<PC_NAME>_DYNAMIC` is set.
+pub fn prefers_static( pc_name: &str, statik: bool ) -> bool {
+    let env_name = pc_name.to_uppercase().replace( '-', "_" );
+    let is_set = |name: &str| env::var_os( name ).is_some();
+    statik || is_set( &format!( "{}_STATIC", env_name )) || (
+        !is_set( &format!( "{}_DYNAMIC", env_name )) && is_set( "PKG_CONFIG_ALL_STATIC" ))
+}
+
+/// The gist of a failure of pkg-config, e.g. "Package tk86 was not found in the
+/// pkg-config search path.", rather than the whole command line and its output.
+pub fn error_summary( err: &pkg_config::Error ) -> String {
+    let (command, output) = match err {
+        pkg_config::Error::Failure{ command, output } |
+        pkg_config::Error::ProbeFailure{ command, output, .. } => (command, output),
+        err => return err.to_string().lines().map( str::trim ).find( |line| !line.is_empty() ).unwrap_or_default().to_owned(),
+    };
+    let stderr = String::from_utf8_lossy( &output.stderr );
+    match stderr.lines().map( str::trim ).find( |line| !line.is_empty() ) {
+        Some( line ) => line.to_owned(),
+        // e.g. a version out of range, for which pkg-config says nothing.
+        None => {
+            let command = command.find( "\"pkg-config\"" ).map( |start| &command[ start.. ]).unwrap_or( command );
+            format!( "{} failed with {}", command, output.status )
+        },
+    }
+}
+
+/// Cargo directives for linking the library found in `pc_name`.pc, without
+/// printing them. Libraries are linked statically if `statik`, `<PC_NAME>_STATIC`
+/// or `PKG_CONFIG_ALL_STATIC` is set and their static archives exist.
+pub fn link_directives( pc_name: &str, library: &pkg_config::Library, statik: bool ) -> Vec<String> {
+    let prefers_static = prefers_static( pc_name, statik );
+    let mut directives = Vec::new();
+
+    for path in &library.link_paths {
+        directives.push( format!( "rustc-link-search=native={}", path.display() ));
+    }
+    for path in &library.framework_paths {
+        directives.push( format!( "rustc-link-search=framework={}", path.display() ));
+    }
+    for lib in &library.libs {
+        let has_archive = library.link_paths
+            .iter()
+            .any( |path| path.join( format!( "lib{}.a", lib )).exists() );
+        if prefers_static && has_archive {
+            directives.push( format!( "rustc-link-lib=static={}", lib ));
+        } else {
+            directives.push( format!( "rustc-link-lib={}", lib ));
+        }
+    }
+    for framework in &library.frameworks {
+        directives.push( format!( "rustc-link-lib=framework={}", framework ));
+    }
+    for file in &library.link_files {
+        directives.push( format!( "rustc-link-arg={}", file.display() ));
+    }
+    for args in &library.ld_args {
+        directives.push( format!( "rustc-link-arg=-Wl,{}", args.join( "," )));
+    }
+    directives
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_words() {
+        assert_eq!( split_words( "  -I/usr/include \t-lz  " ), [ "-I/usr/include", "-lz" ]);
+        assert_eq!( split_words( r#"-DNAME="a b" -I'/opt/my dir'"# ), [ "-DNAME=a b", "-I/opt/my dir" ]);
+        assert_eq!( split_words( r#"-I/opt/my\ dir -DQ=\"x\""# ), [ "-I/opt/my dir", r#"-DQ="x""# ]);
+        assert_eq!( split_words( r#"'C:\include' "a\"b""# ), [ r"C:\include", r#"a"b"# ]);
+        assert_eq!( split_words( r#"-a "" ''"# ), [ "-a", "", "" ]);
+        assert!( split_words( "" ).is_empty() );
+    }
+
+    #[cfg( unix )]
+    #[test]
+    fn fails_with_pkg_config() {
+        let _env = crate::overrides::TEST_ENV.lock().unwrap();
+        env::set_var( "PKG_CONFIG", "false" );
+        assert!( variable( "zlib", "includedir" ).is_err() );
+        assert!( requires( "zlib", false ).is_err() );
+        assert!( cflags( "zlib" ).is_err() );
+        env::remove_var( "PKG_CONFIG" );
+    }
+}
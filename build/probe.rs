@@ -0,0 +1,1337 @@
+//! Probing libraries and their dependencies into a graph, each library once.
+
+use crate::{
+    UTF8_PATH,
+    cmake,
+    compiler,
+    configscript::{self, Flags},
+    overrides::{self, Overrides},
+    pcfile,
+    pkgconf,
+    report,
+    search,
+    spec::{Dependency, LibGroup, PROBE_STRATEGIES, Spec},
+    version::{self, VersionReq},
+};
+
+use anyhow::{
+    Context,
+    Result,
+    anyhow,
+};
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, btree_map::Entry},
+    env,
+    error::Error,
+    fmt::{self, Debug, Display},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+/// A library probed successfully, and the edges to its dependencies.
+#[derive( Debug, Default )]
+pub struct Resolved {
+    pub found_by            : String, // the probe strategy
+    pub found_in            : String, // where the library matched, e.g. the .pc file or the executable
+    pub pc_name             : Option<String>,
+    pub version             : Option<String>,
+    pub include_dir         : String,
+    pub include_paths       : Vec<String>,
+    pub headers             : Vec<String>,
+    pub clang_args          : Vec<String>,
+    pub link_directives     : Vec<String>,
+    pub requires            : Vec<(String,Option<VersionReq>)>, // `Requires` of the .pc file
+    pub requires_private    : Vec<(String,Option<VersionReq>)>, // `Requires.private` of the .pc file
+    pub dependencies        : Vec<String>,
+    pub header_dependencies : Vec<String>,
+}
+
+impl Resolved {
+    /// Whether any of its link directives links a static archive, e.g.
+    /// "rustc-link-lib=static=z".
+    pub fn links_static_archive( &self ) -> bool {
+        self.link_directives.iter().any( |directive| directive.starts_with( "rustc-link-lib=static=" ))
+    }
+}
+
+/// A dependency to resolve, declared in the spec or derived from the .pc file.
+struct Edge {
+    name     : String,
+    version  : Option<VersionReq>,
+    declared : bool,
+}
+
+/// Dependencies which eventually depend on the library itself.
+#[derive( Debug )]
+struct CycleError( Vec<String> );
+
+impl Display for CycleError {
+    fn fmt( &self, f: &mut fmt::Formatter ) -> fmt::Result {
+        write!( f, "dependency cycle detected: {}", self.0.join( " -> " ))
+    }
+}
+
+impl Error for CycleError {}
+
+/// What became of a probe strategy tried for a library.
+#[derive( Debug )]
+pub enum Outcome {
+    Skipped( String ), // why the strategy does not apply to the library
+    Failed( String ),
+    Succeeded,
+}
+
+/// A probe strategy tried for a library, and what it looked for.
+#[derive( Debug )]
+pub struct Attempt {
+    pub strategy : String,
+    pub tried    : Vec<String>,
+    pub outcome  : Outcome,
+    pub elapsed  : Duration,
+}
+
+/// A library probed for the version requirement of its dependent, if any, e.g.
+/// `("zlib", Some( ">=1.2.13" ))`, which may fail while `("zlib", None)` succeeds.
+pub type ProbeKey = (String,Option<String>);
+
+fn probe_key( pkg_name: &str, version_req: Option<&VersionReq> ) -> ProbeKey {
+    (pkg_name.to_owned(), version_req.map( ToString::to_string ))
+}
+
+#[derive( Debug )]
+pub struct LibInfo {
+    pub specs    : HashMap<String,Spec>,
+    builds       : HashSet<String>,                     // libraries in `build`
+    pub resolved : RefCell<HashMap<String,Resolved>>, // pkg name -> the probed library
+    pub attempts : RefCell<HashMap<ProbeKey,Vec<Attempt>>>, // the strategies tried in order
+    pub failed   : RefCell<HashMap<ProbeKey,String>>,   // why it failed
+    failed_deps  : RefCell<HashMap<ProbeKey,ProbeKey>>, // the dependency failing it
+    not_found    : RefCell<HashMap<String,ProbeKey>>,   // pkg name -> the probe not finding it of any version
+    probe_order  : Option<Vec<String>>,                 // from `CLIB_PROBE_ORDER`
+}
+
+impl LibInfo {
+    pub fn new( specs: HashMap<String,Spec>, builds: HashSet<String>, probe_order: Option<Vec<String>> ) -> Self {
+        LibInfo {
+            specs       ,
+            builds      ,
+            resolved    : RefCell::default(),
+            attempts    : RefCell::default(),
+            failed      : RefCell::default(),
+            failed_deps : RefCell::default(),
+            not_found   : RefCell::default(),
+            probe_order ,
+        }
+    }
+
+    /// Probes the library and its dependencies, recursively. Libraries already
+    /// probed, successfully or not, are not probed again, unless they have failed
+    /// for another version requirement.
+    pub fn probe( &self, pkg_name: &str ) -> Result<()> {
+        self.resolve( pkg_name, None, &mut Vec::new() )
+    }
+
+    /// Resolves the library, which should meet `version_req` of its dependent in
+    /// addition to its own spec's. The `path` lists the dependents being resolved.
+    fn resolve( &self, pkg_name: &str, version_req: Option<&VersionReq>, path: &mut Vec<String> ) -> Result<()> {
+        if let Some( start ) = path.iter().position( |name| name == pkg_name ) {
+            let mut cycle = path[ start.. ].to_vec();
+            cycle.push( pkg_name.to_owned() );
+            return Err( anyhow::Error::new( CycleError( cycle )));
+        }
+
+        let key = probe_key( pkg_name, version_req );
+        if let Some( err ) = self.failed.borrow().get( &key ) {
+            return Err( anyhow!( "{}", err ));
+        }
+
+        // A library not found for one version requirement is not found for others either.
+        let not_found = self.not_found.borrow().get( pkg_name ).and_then( |probed_as| self.failed.borrow().get( probed_as ).cloned() );
+        if let Some( err ) = not_found {
+            self.failed.borrow_mut().insert( key, err.clone() );
+            return Err( anyhow!( "{}", err ));
+        }
+
+        let resolved_version = self.resolved.borrow().get( pkg_name ).map( |resolved| resolved.version.clone() );
+        let result = match resolved_version {
+            Some( version ) => match (version_req, version) {
+                (Some( version_req ), Some( version )) => version_req.check( pkg_name, &version, "the probed library" ),
+                (Some( version_req ), None) => Err( anyhow!( "failed to determine the version of {}, which is required to be `{}`",
+                    pkg_name, version_req )),
+                (None, _) => Ok(()),
+            },
+            None => {
+                path.push( pkg_name.to_owned() );
+                let result = self.resolve_once( pkg_name, version_req, path );
+                path.pop();
+                result.map( |resolved| {
+                    self.resolved.borrow_mut().insert( pkg_name.to_owned(), resolved );
+                })
+            },
+        };
+
+        if let Err( err ) = &result {
+            self.failed.borrow_mut().insert( key, format!( "{:#}", err ));
+        }
+        result
+    }
+
+    fn resolve_once( &self, pkg_name: &str, version_req: Option<&VersionReq>, path: &mut Vec<String> ) -> Result<Resolved> {
+        let key = probe_key( pkg_name, version_req );
+        let spec = self.specs.get( pkg_name );
+
+        let version_req = match (spec.and_then( |spec| spec.version.as_ref() ), version_req) {
+            (Some( own ), Some( inherited )) => Some( own.and( inherited )),
+            (own, inherited) => own.or( inherited ).cloned(),
+        };
+        let version_req = version_req.as_ref();
+
+        let overrides = Overrides::of( pkg_name );
+
+        let statik = overrides.statik;
+        let env_name = overrides::env_name( pkg_name );
+        let has_cmake = spec.is_some_and( |spec| spec.cmake.is_some() );
+        let has_config_script = spec.is_some_and( |spec| !spec.config_script.is_empty() || spec.config_file.is_some() );
+        let has_libs = spec.is_some_and( |spec| !spec.libs.is_empty() );
+        let has_vendored = spec.is_some_and( |spec| spec.vendored.is_some() );
+
+        let probe_order = self.probe_order( pkg_name );
+        let mut probed = None;
+        let mut failures = Vec::<String>::new();
+        let mut attempts = Vec::<Attempt>::new();
+        let mut env_failure = None;
+
+        version::take_rejected();
+        for strategy in &probe_order {
+            let skipped = match strategy.as_str() {
+                "env"           if !overrides.locates()     => Some( match overrides.include_dir {
+                    Some(_) => format!( "CLIB_{0}_INCLUDE_DIR is set, but neither CLIB_{0}_PREFIX nor CLIB_{0}_LIB_DIR", env_name ),
+                    None    => format!( "none of CLIB_{0}_PREFIX, CLIB_{0}_INCLUDE_DIR and CLIB_{0}_LIB_DIR is set", env_name ),
+                }),
+                "env"           if !has_libs                => Some( format!( "no `libs` in spec to link from CLIB_{}_*", env_name )),
+                "pkg-config"    if overrides.no_pkg_config  => Some( format!( "disabled by CLIB_{}_NO_PKG_CONFIG", env_name )),
+                "cmake"         if !has_cmake               => Some( "no `cmake` in spec".to_owned() ),
+                "config-script" if !has_config_script       => Some( "no `config-script` or `config-file` in spec".to_owned() ),
+                "compiler"      if !has_libs                => Some( "no `libs` in spec".to_owned() ),
+                "search"        if !has_libs                => Some( "no `libs` in spec".to_owned() ),
+                "vendored"      if !has_vendored            => Some( "no `vendored` in spec".to_owned() ),
+                _                                           => None,
+            };
+            if let Some( reason ) = skipped {
+                attempts.push( Attempt{ strategy: strategy.clone(), tried: Vec::new(), outcome: Outcome::Skipped( reason ), elapsed: Duration::ZERO });
+                continue;
+            }
+
+            let mut tried = Vec::new();
+            let start = Instant::now();
+            let result = match strategy.as_str() {
+                "env"           => self.probe_via_env( pkg_name, version_req, &overrides, &mut tried ),
+                "pkg-config"    => self.probe_via_pkgconf( pkg_name, version_req, statik, &mut tried ),
+                "cmake"         => self.probe_via_cmake( pkg_name, version_req, statik, &mut tried ),
+                "config-script" => self.probe_via_config_script( pkg_name, version_req, statik, &mut tried ),
+                "compiler"      => self.probe_via_compiler( pkg_name, version_req, statik, &mut tried ),
+                "search"        => self.probe_via_search( pkg_name, version_req, statik, &mut tried ),
+                "vendored"      => self.probe_via_vendored( pkg_name, version_req, statik, &mut tried ),
+                _               => unreachable!( "probe strategies should have been checked" ),
+            };
+            let elapsed = start.elapsed();
+            match result {
+                Ok( resolved ) => {
+                    attempts.push( Attempt{ strategy: strategy.clone(), tried, outcome: Outcome::Succeeded, elapsed });
+                    probed = Some( Resolved{ found_by: strategy.clone(), ..resolved });
+                    break;
+                },
+                Err( err ) => {
+                    attempts.push( Attempt{ strategy: strategy.clone(), tried, outcome: Outcome::Failed( format!( "{:#}", err )), elapsed });
+                    // The library located by environment variables is not looked for elsewhere.
+                    if strategy == "env" {
+                        env_failure = Some( err );
+                        break;
+                    }
+                    failures.push( err.to_string() );
+                },
+            }
+        }
+
+        // Unless some version is rejected, it is not found for any version requirement.
+        if probed.is_none() && !version::take_rejected() {
+            self.not_found.borrow_mut().insert( pkg_name.to_owned(), key.clone() );
+        }
+        self.attempts.borrow_mut().insert( key.clone(), attempts );
+        if let Some( err ) = env_failure {
+            return Err( err );
+        }
+
+        let mut resolved = probed.ok_or_else( || if failures.is_empty() {
+            anyhow!( "none of the probe strategies {:?} applies", probe_order )
+        } else {
+            anyhow!( "{}", failures.join( "; " ))
+        })?;
+
+        let mut dependencies = Vec::<Edge>::new();
+        let mut header_dependencies = Vec::<Edge>::new();
+
+        // Dependencies without headers in spec are linked only, unless in `build` too.
+        let headers = match (spec, &resolved.pc_name) {
+            (Some( spec ), _) => spec.headers.clone(),
+            (None, Some( pc_name )) if self.builds.contains( pkg_name ) => {
+                let headers = search::guess_headers( Path::new( &resolved.include_dir ), &[ pkg_name, pc_name ]);
+                if headers.is_empty() {
+                    report::emit( format!( "warning=[clib] no headers of {} found in {}, which may be specified in `build`",
+                        pkg_name, resolved.include_dir ));
+                }
+                headers
+            },
+            (None, _) => Vec::new(),
+        };
+        resolved.headers = headers
+            .iter()
+            .map( |header| Path::new( &resolved.include_dir )
+                .join( header )
+                .to_str()
+                .context( UTF8_PATH )
+                .map( ToOwned::to_owned ))
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Some( spec ) = spec {
+
+            let declared = |deps: &[Dependency]| deps
+                .iter()
+                .filter( |dep| dep.is_enabled() )
+                .map( |dep| Edge{ name: dep.name.clone(), version: dep.version.clone(), declared: true })
+                .collect::<Vec<_>>();
+            dependencies = declared( &spec.dependencies );
+            header_dependencies = declared( &spec.header_dependencies );
+        }
+
+        // `Requires` are linked, while `Requires.private` may provide headers only.
+        let requires = resolved.requires
+            .iter()
+            .map( |required| (required, false) )
+            .chain( resolved.requires_private.iter().map( |required| (required, true) ));
+        for ((pc_name, version), private) in requires {
+            if let Some( name ) = self.spec_name_of( pc_name ).filter( |name| *name != pkg_name ) {
+                if !dependencies.iter().chain( &header_dependencies ).any( |edge| edge.name == *name ) {
+                    let edge = Edge{ name: name.clone(), version: version.clone(), declared: false };
+                    if private {
+                        header_dependencies.push( edge );
+                    } else {
+                        dependencies.push( edge );
+                    }
+                }
+            }
+        }
+
+        for (edges, is_header_dep) in [ (dependencies, false), (header_dependencies, true) ] {
+            for edge in edges {
+                match self.resolve( &edge.name, edge.version.as_ref(), path ) {
+                    Ok(_) => if is_header_dep {
+                        resolved.header_dependencies.push( edge.name );
+                    } else {
+                        resolved.dependencies.push( edge.name );
+                    },
+                    Err( err ) => match &resolved.pc_name {
+                        // The .pc file has taken care of its dependencies.
+                        Some( pc_name ) if !err.is::<CycleError>() && (!is_header_dep || !edge.declared) => report::emit( format!(
+                            "warning=[clib] dependency {} of {} is not probed, relying on {}.pc instead: {:#}",
+                            edge.name, pkg_name, pc_name, err )),
+                        _ => {
+                            self.failed_deps.borrow_mut().insert( key, probe_key( &edge.name, edge.version.as_ref() ));
+                            return Err( err );
+                        },
+                    },
+                }
+            }
+        }
+
+        Ok( resolved )
+    }
+
+    /// A readable report of why the library failed: every strategy tried for it, what
+    /// each one looked for, and likewise for the dependency failing it, if any.
+    pub fn failure_report( &self, key: &ProbeKey ) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut visited = HashSet::new();
+        let mut key = key.clone();
+        let mut indent = String::new();
+
+        loop {
+            let failed = self.failed.borrow().get( &key ).cloned().unwrap_or_default();
+            let required = key.1.as_ref().map( |version_req| format!( " `{}`", version_req )).unwrap_or_default();
+            lines.push( format!( "{}fails to probe library {}{}: {}", indent, key.0, required, failed ));
+            if !visited.insert( key.clone() ) {
+                break;
+            }
+
+            // The strategies tried for another version requirement, if not found then.
+            let attempts = self.attempts.borrow();
+            let attempts = attempts.get( &key ).or_else( || self.not_found
+                .borrow()
+                .get( &key.0 )
+                .and_then( |probed_as| attempts.get( probed_as )));
+            for attempt in attempts.into_iter().flatten() {
+                let outcome = match &attempt.outcome {
+                    Outcome::Skipped( reason ) => format!( "skipped, {}", reason ),
+                    Outcome::Failed( err ) => err.clone(),
+                    Outcome::Succeeded => "succeeded".to_owned(),
+                };
+                lines.push( format!( "{}  {}: {}", indent, attempt.strategy, outcome ));
+                lines.extend( attempt.tried.iter().map( |tried| format!( "{}    - {}", indent, tried )));
+            }
+
+            match self.failed_deps.borrow().get( &key ) {
+                Some( dep ) => {
+                    lines.push( format!( "{}  because its dependency {} failed:", indent, dep.0 ));
+                    key = dep.clone();
+                    indent.push_str( "    " );
+                },
+                None => break,
+            }
+        }
+        lines
+    }
+
+    /// Resolves the library by its `bundled-include-dir` without probing, for building
+    /// docs only: nothing is linked. Its dependencies are resolved so too, if they have
+    /// bundled headers.
+    pub fn resolve_for_docs( &self, pkg_name: &str ) -> Result<()> {
+        self.resolve_bundled( pkg_name, &mut Vec::new() )
+    }
+
+    /// Resolves the library for docs. The `path` lists the dependents being resolved.
+    fn resolve_bundled( &self, pkg_name: &str, path: &mut Vec<String> ) -> Result<()> {
+        if let Some( start ) = path.iter().position( |name| name == pkg_name ) {
+            let mut cycle = path[ start.. ].to_vec();
+            cycle.push( pkg_name.to_owned() );
+            return Err( anyhow::Error::new( CycleError( cycle )));
+        }
+        if self.resolved.borrow().contains_key( pkg_name ) {
+            return Ok(());
+        }
+        let spec = self.specs.get( pkg_name ).context( "no spec for the library" )?;
+        let include_dir = spec.bundled_include_dir
+            .as_ref()
+            .context( "neither `for-docs-rs` nor `bundled-include-dir` in spec" )?;
+
+        let headers = spec.headers
+            .iter()
+            .map( |header| include_dir.join( header ))
+            .map( |header| if header.is_file() {
+                header.to_str().context( UTF8_PATH ).map( ToOwned::to_owned )
+            } else {
+                Err( anyhow!( "bundled header {} not found", header.display() ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // A cycle fails every library in it, while other failing dependencies are left out.
+        path.push( pkg_name.to_owned() );
+        let mut resolve_deps = |deps: &[Dependency]| {
+            let mut names = Vec::new();
+            for dep in deps.iter().filter( |dep| dep.is_enabled() ) {
+                match self.resolve_bundled( &dep.name, path ) {
+                    Ok(_) => names.push( dep.name.clone() ),
+                    Err( err ) if err.is::<CycleError>() => return Err( err ),
+                    Err( err ) => report::emit( format!( "warning=[clib] dependency {} of {} has no bundled headers: {:#}",
+                        dep.name, pkg_name, err )),
+                }
+            }
+            Ok( names )
+        };
+        let dependencies = resolve_deps( &spec.dependencies )?;
+        let header_dependencies = resolve_deps( &spec.header_dependencies )?;
+        path.pop();
+
+        self.resolved.borrow_mut().insert( pkg_name.to_owned(), Resolved {
+            found_by            : "docs-only".to_owned(),
+            found_in            : include_dir.display().to_string(),
+            include_dir         : include_dir.to_str().context( UTF8_PATH )?.to_owned(),
+            headers             ,
+            dependencies        ,
+            header_dependencies ,
+            ..Resolved::default()
+        });
+        Ok(())
+    }
+
+    /// The order of probe strategies of the library: `CLIB_PROBE_ORDER`, or else the
+    /// `probe` of its spec, or else the default one.
+    fn probe_order( &self, pkg_name: &str ) -> Vec<String> {
+        self.probe_order
+            .clone()
+            .or_else( || self.specs.get( pkg_name ).and_then( |spec| spec.probe.clone() ))
+            .unwrap_or_else( || PROBE_STRATEGIES.iter().map( |strategy| strategy.to_string() ).collect() )
+    }
+
+    /// The library of which `pc_name` is the .pc file: the one named so, or else the
+    /// first one by name having it in `pc-alias`.
+    fn spec_name_of( &self, pc_name: &str ) -> Option<&String> {
+        match self.specs.get_key_value( pc_name ) {
+            Some(( name, _ )) => Some( name ),
+            None => self.specs
+                .iter()
+                .filter( |(_, spec)| spec.pc_alias.iter().any( |alias| alias == pc_name ))
+                .map( |(name, _)| name )
+                .min(),
+        }
+    }
+
+    /// Locates the library by `CLIB_<NAME>_PREFIX`, `CLIB_<NAME>_INCLUDE_DIR` and
+    /// `CLIB_<NAME>_LIB_DIR`, linking the `libs` of its spec found in the lib dir.
+    fn probe_via_env( &self, pkg_name: &str, version_req: Option<&VersionReq>, overrides: &Overrides, tried: &mut Vec<String> ) -> Result<Resolved> {
+        let env_name = overrides::env_name( pkg_name );
+        let spec = self.specs
+            .get( pkg_name )
+            .filter( |spec| !spec.libs.is_empty() )
+            .context( "metadata should contain libs" )?;
+
+        let include_dir = overrides.include_dir.clone().or_else( || overrides.prefix
+            .as_ref()
+            .map( |prefix| search::include_dir( prefix, &spec.include_root, &spec.includedir )));
+        let include_dir = match include_dir {
+            Some( include_dir ) => include_dir.to_str().context( UTF8_PATH )?.to_owned(),
+            None if spec.headers.is_empty() => String::new(),
+            None => return Err( anyhow!( "CLIB_{0}_INCLUDE_DIR or CLIB_{0}_PREFIX should be set to locate headers", env_name )),
+        };
+
+        let lib_dirs = match (&overrides.lib_dir, &overrides.prefix) {
+            (Some( lib_dir ), _) => vec![ lib_dir.clone() ],
+            (None, Some( prefix )) => search::lib_dirs( prefix, &spec.libdir ),
+            (None, None) => return Err( anyhow!( "CLIB_{0}_LIB_DIR or CLIB_{0}_PREFIX should be set to locate libs", env_name )),
+        };
+        tried.push( format!( "include dir {:?}, lib dirs {:?}", include_dir, lib_dirs ));
+
+        let exe = overrides.prefix.as_ref().and_then( |prefix| exe_in_prefix( spec, prefix ));
+        let version = search_version( spec, exe.as_deref(), &include_dir );
+        check_version( pkg_name, version_req, version.as_deref(), &format!( "CLIB_{}_*", env_name ))?;
+
+        let link_directives = link_directives_for_libs( &lib_dirs, spec.libs.iter().chain( &spec.libs_private ), overrides.statik )?;
+
+        Ok( Resolved {
+            found_in        : format!( "CLIB_{}_*", env_name ),
+            version         ,
+            include_dir     ,
+            link_directives ,
+            ..Resolved::default()
+        })
+    }
+
+    /// Locates the library in its `vendored` install prefix, shipped with the
+    /// downstream crate, linking the `libs` of its spec found there.
+    fn probe_via_vendored( &self, pkg_name: &str, version_req: Option<&VersionReq>, statik: bool, tried: &mut Vec<String> ) -> Result<Resolved> {
+        let spec = self.specs.get( pkg_name ).context( "no spec for the vendored library" )?;
+        let prefix = spec.vendored.as_ref().context( "no `vendored` in spec" )?;
+        if spec.libs.is_empty() {
+            return Err( anyhow!( "metadata should contain libs" ));
+        }
+        tried.push( format!( "vendored prefix {}", prefix.display() ));
+        if !prefix.is_dir() {
+            return Err( anyhow!( "vendored prefix {} not found", prefix.display() ));
+        }
+
+        let include_dir = search::include_dir( prefix, &spec.include_root, &spec.includedir )
+            .to_str()
+            .context( UTF8_PATH )?
+            .to_owned();
+        let version = search_version( spec, exe_in_prefix( spec, prefix ).as_deref(), &include_dir );
+        check_version( pkg_name, version_req, version.as_deref(), &prefix.display().to_string() )?;
+
+        let link_directives = link_directives_for_libs( &search::lib_dirs( prefix, &spec.libdir ), spec.libs.iter().chain( &spec.libs_private ), statik )?;
+
+        Ok( Resolved {
+            found_in        : prefix.display().to_string(),
+            version         ,
+            include_dir     ,
+            link_directives ,
+            ..Resolved::default()
+        })
+    }
+
+    fn probe_via_pkgconf( &self, pkg_name: &str, version_req: Option<&VersionReq>, statik: bool, tried: &mut Vec<String> ) -> Result<Resolved> {
+        env::set_var( "PKG_CONFIG_ALLOW_SYSTEM_CFLAGS", "1" );
+        env::set_var( "PKG_CONFIG_ALLOW_SYSTEM_LIBS", "1" );
+
+        let mut cfg = pkg_config::Config::new();
+        cfg.cargo_metadata( false );
+        if statik {
+            cfg.statik( true );
+        }
+        if let Some( version_req ) = version_req {
+            cfg.range_version( version_req.range() );
+        }
+
+        for var in [ "PKG_CONFIG_PATH", "PKG_CONFIG_LIBDIR", "PKG_CONFIG_SYSROOT_DIR" ] {
+            if let Some( value ) = env::var_os( var ) {
+                tried.push( format!( "{}={}", var, value.to_string_lossy() ));
+            }
+        }
+
+        let pc_names = self.pc_names( pkg_name );
+        let mut names = pc_names.iter();
+        let mut mismatch = None;
+        let (library, pc_name) = loop {
+            if let Some( &name ) = names.next() {
+                match cfg.probe( name ) {
+                    Ok( library ) => break (library, name.to_owned() ),
+                    Err( pkg_config::Error::Command{ .. }) => {
+                        tried.push( format!( "pkg-config is not available, reading .pc files in {:?}", pcfile::search_dirs() ));
+                        return self.probe_via_pcfile( pkg_name, version_req, statik, tried );
+                    },
+                    Err( err ) => {
+                        tried.push( format!( "{}.pc: {}", name, pkgconf::error_summary( &err )));
+                        if let Some( version_req ) = version_req {
+                            if let Ok( found ) = pkgconf::modversion( name ) {
+                                mismatch.get_or_insert( version_req.check( pkg_name, &found, &format!( "{}.pc", name )));
+                            }
+                        }
+                    },
+                }
+            } else {
+                return Err( mismatch
+                    .and_then( Result::err )
+                    .unwrap_or_else( || anyhow!( "failed to locate {}", pc_file_names( &pc_names ))));
+            }
+        };
+
+        Ok( Resolved {
+            found_in         : Path::new( &pkgconf::variable( &pc_name, "pcfiledir" )? )
+                .join( format!( "{}.pc", pc_name ))
+                .display()
+                .to_string(),
+            version          : Some( library.version.clone() ).filter( |version| !version.is_empty() ),
+            include_dir      : pkgconf::variable( &pc_name, "includedir" )?,
+            include_paths    : library.include_paths
+                .iter()
+                .map( |path| path.to_str().context( UTF8_PATH ).map( ToOwned::to_owned ))
+                .collect::<Result<_>>()?,
+            clang_args       : pkgconf::cflags( &pc_name )?,
+            link_directives  : pkgconf::link_directives( &pc_name, &library, statik ),
+            requires         : pkgconf::requires( &pc_name, false )?,
+            requires_private : pkgconf::requires( &pc_name, true )?,
+            pc_name          : Some( pc_name ),
+            ..Resolved::default()
+        })
+    }
+
+    /// Probes the library by reading .pc files, if the `pkg-config` executable is not available.
+    fn probe_via_pcfile( &self, pkg_name: &str, version_req: Option<&VersionReq>, statik: bool, tried: &mut Vec<String> ) -> Result<Resolved> {
+        let mut failure = None;
+
+        for name in self.pc_names( pkg_name ) {
+            match pcfile::probe( name, version_req, statik ) {
+                Ok( package ) => return Ok( Resolved {
+                    found_in         : package.path.display().to_string(),
+                    version          : Some( package.version ).filter( |version| !version.is_empty() ),
+                    include_dir      : package.includedir,
+                    include_paths    : package.cflags
+                        .iter()
+                        .filter_map( |flag| flag.strip_prefix( "-I" ))
+                        .map( ToOwned::to_owned )
+                        .collect(),
+                    link_directives  : pcfile::link_directives( name, &package.libs, statik ),
+                    clang_args       : pkgconf::clang_args( package.cflags ),
+                    requires         : package.requires,
+                    requires_private : package.requires_private,
+                    pc_name          : Some( name.to_owned() ),
+                    ..Resolved::default()
+                }),
+                Err( err ) => {
+                    tried.push( format!( "{:#}", err ));
+                    failure.get_or_insert( err );
+                },
+            }
+        }
+        Err( failure.unwrap_or_else( || anyhow!( "failed to locate {}", pc_file_names( &self.pc_names( pkg_name )))))
+    }
+
+    /// Names of the .pc file of the library: its own name, then `pc-alias`.
+    fn pc_names<'a>( &'a self, pkg_name: &'a str ) -> Vec<&'a str> {
+        let mut pc_names = vec![ pkg_name ];
+        if let Some( spec ) = self.specs.get( pkg_name ) {
+            pc_names.extend( spec.pc_alias.iter().map( String::as_str ));
+        }
+        pc_names
+    }
+
+    /// Locates the `headers` and `libs` of the library in the directories searched by
+    /// the C compiler, including `CPATH`, `C_INCLUDE_PATH` and `LIBRARY_PATH`.
+    fn probe_via_compiler( &self, pkg_name: &str, version_req: Option<&VersionReq>, statik: bool, tried: &mut Vec<String> ) -> Result<Resolved> {
+        let spec = self.specs.get( pkg_name ).context( "no spec for searching the library" )?;
+        let search_dirs = compiler::search_dirs()?;
+        tried.push( format!( "include dirs of the compiler {:?}", search_dirs.include_dirs ));
+        tried.push( format!( "lib dirs of the compiler {:?}", search_dirs.lib_dirs ));
+
+        let include_dir = if spec.headers.is_empty() {
+            String::new()
+        } else {
+            search_dirs.include_dirs
+                .iter()
+                .flat_map( |root| spec.includedir.iter().map( |dir| root.join( dir )).chain( Some( root.clone() )))
+                .find( |dir| spec.headers.iter().all( |header| dir.join( header ).is_file() ))
+                .context( "headers not found in the include dirs of the compiler" )?
+                .to_str()
+                .context( UTF8_PATH )?
+                .to_owned()
+        };
+
+        let version = search_version( spec, None, &include_dir );
+        check_version( pkg_name, version_req, version.as_deref(), "the search dirs of the compiler" )?;
+
+        let link_directives = link_directives_for_libs( &search_dirs.lib_dirs, spec.libs.iter().chain( &spec.libs_private ), statik )?;
+
+        Ok( Resolved {
+            found_in        : "the search dirs of the compiler".to_owned(),
+            version         ,
+            include_dir     ,
+            link_directives ,
+            ..Resolved::default()
+        })
+    }
+
+    /// Searches the library in the install prefixes of its `exe` found in `PATH`,
+    /// and on Unix, in the standard ones too.
+    fn probe_via_search( &self, pkg_name: &str, version_req: Option<&VersionReq>, statik: bool, tried: &mut Vec<String> ) -> Result<Resolved> {
+        let spec = self.specs.get( pkg_name ).context( "no spec for searching the library" )?;
+
+        if spec.libs.is_empty() {
+            return Err( anyhow!( "metadata should contain libs" ));
+        }
+
+        let mut failure = None;
+        let candidates = candidate_prefixes( spec, &mut failure, tried );
+
+        for (prefix, exe) in &candidates {
+            // A prefix not valid UTF-8, e.g. some dir in `/opt`, is not a candidate.
+            let Some( guess_include ) = search::include_dir( prefix, &spec.include_root, &spec.includedir )
+                .to_str()
+                .map( ToOwned::to_owned )
+            else {
+                tried.push( format!( "{}: {}", prefix.display(), UTF8_PATH ));
+                continue;
+            };
+
+            // Without an executable, the headers are the evidence of the library.
+            if exe.is_none() && !spec.headers.iter().all( |header| Path::new( &guess_include ).join( header ).exists() ) {
+                tried.push( format!( "{}: headers {:?} not found in {}", prefix.display(), spec.headers, guess_include ));
+                continue;
+            }
+
+            let found_in = exe.as_deref().unwrap_or( prefix ).display().to_string();
+            let version = search_version( spec, exe.as_deref(), &guess_include );
+            if let Err( err ) = check_version( pkg_name, version_req, version.as_deref(), &found_in ) {
+                tried.push( format!( "{}: {}", prefix.display(), err ));
+                failure.get_or_insert( err );
+                continue;
+            }
+
+            match link_directives_for_libs( &search::lib_dirs( prefix, &spec.libdir ), spec.libs.iter().chain( &spec.libs_private ), statik ) {
+                Ok( link_directives ) => return Ok( Resolved {
+                    found_in        ,
+                    version         ,
+                    include_dir     : guess_include,
+                    link_directives ,
+                    ..Resolved::default()
+                }),
+                Err( err ) => {
+                    tried.push( format!( "{}: {}", prefix.display(), err ));
+                    failure.get_or_insert( err );
+                },
+            }
+        }
+
+        Err( failure.unwrap_or_else( || if cfg!( unix ) {
+            anyhow!( "neither executable nor headers found" )
+        } else {
+            anyhow!( "executable not found" )
+        }))
+    }
+
+    /// Probes the library by `find_package()` of its `cmake` package in a throwaway
+    /// CMake project, using the usage requirements of its imported targets.
+    fn probe_via_cmake( &self, pkg_name: &str, version_req: Option<&VersionReq>, statik: bool, tried: &mut Vec<String> ) -> Result<Resolved> {
+        let spec = self.specs.get( pkg_name ).context( "no spec for probing by cmake" )?;
+        let cmake = spec.cmake.as_ref().context( "no cmake package in spec" )?;
+
+        tried.push( format!( "find_package( {} ) with targets {:?}", cmake.package, cmake.targets ));
+        let package = cmake::probe( pkg_name, cmake )?;
+        check_version( pkg_name, version_req, package.version.as_deref(), &format!( "cmake package {}", cmake.package ))?;
+
+        Ok( Resolved {
+            found_in        : format!( "cmake package {}", cmake.package ),
+            version         : package.version,
+            include_dir     : package.include_dirs.first().cloned().unwrap_or_default(),
+            include_paths   : package.include_dirs,
+            clang_args      : pkgconf::clang_args( package.cflags ),
+            link_directives : pcfile::link_directives( pkg_name, &package.libs, statik ),
+            ..Resolved::default()
+        })
+    }
+
+    /// Probes the library by its `config-script` found in `PATH`, e.g.
+    /// `curl-config --cflags --libs`, or else by its `config-file`, e.g. tclConfig.sh,
+    /// found in the lib dirs of the install prefixes searched for the library.
+    fn probe_via_config_script( &self, pkg_name: &str, version_req: Option<&VersionReq>, statik: bool, tried: &mut Vec<String> ) -> Result<Resolved> {
+        let spec = self.specs.get( pkg_name ).context( "no spec for probing by config scripts" )?;
+        let mut failure = None;
+
+        for name in &spec.config_script {
+            let Some( script ) = which( name ) else {
+                tried.push( format!( "{} not found in PATH", name ));
+                continue;
+            };
+            let found_in = script.display().to_string();
+            let probed = configscript::run( &script ).and_then( |flags| {
+                check_version( pkg_name, version_req, flags.version.as_deref(), &found_in )?;
+                resolved_by_flags( pkg_name, spec, flags, &found_in, search::prefix_of( &script, &spec.bindir ).as_deref(), statik )
+            });
+            match probed {
+                Ok( resolved ) => return Ok( resolved ),
+                Err( err ) => {
+                    tried.push( format!( "{}: {}", found_in, err ));
+                    failure.get_or_insert( err );
+                },
+            }
+        }
+
+        if let Some( config_file ) = &spec.config_file {
+            let mut searched = Vec::new();
+            for (prefix, _) in candidate_prefixes( spec, &mut None, &mut Vec::new() ) {
+                // e.g. "lib/tclConfig.sh" or "lib/tcl8.6/tclConfig.sh"
+                let dirs = search::lib_dirs( &prefix, &spec.libdir )
+                    .into_iter()
+                    .flat_map( |lib_dir| spec.includedir
+                        .iter()
+                        .map( |dir| lib_dir.join( dir ))
+                        .chain( Some( lib_dir.clone() ))
+                        .collect::<Vec<_>>() )
+                    .collect::<Vec<_>>();
+                let paths = dirs
+                    .iter()
+                    .flat_map( |dir| config_file.name.iter().map( move |name| dir.join( name )))
+                    .filter( |path| path.is_file() )
+                    .collect::<Vec<_>>();
+                searched.extend( dirs );
+
+                for path in paths {
+                    let found_in = path.display().to_string();
+                    let probed = configscript::read( &path, config_file, statik ).and_then( |flags| {
+                        check_version( pkg_name, version_req, flags.version.as_deref(), &found_in )?;
+                        resolved_by_flags( pkg_name, spec, flags, &found_in, Some( &prefix ), statik )
+                    });
+                    match probed {
+                        Ok( resolved ) => return Ok( resolved ),
+                        Err( err ) => {
+                            tried.push( format!( "{}: {}", found_in, err ));
+                            failure.get_or_insert( err );
+                        },
+                    }
+                }
+            }
+            tried.push( format!( "looked for {:?} in {:?}", config_file.name, searched ));
+        }
+
+        Err( failure.unwrap_or_else( || anyhow!( "neither config script nor config file found" )))
+    }
+
+    /// Libraries to link for `roots`: themselves and their `dependencies`,
+    /// recursively, sorted topologically: each library precedes its dependencies,
+    /// and libraries not depending on each other are sorted by name.
+    pub fn link_order( &self, roots: &[&String] ) -> Vec<String> {
+        let resolved = self.resolved.borrow();
+        let mut dependents = BTreeMap::<String,Vec<String>>::new();
+        let mut visited = HashSet::new();
+        let mut pending = roots.iter().map( |root| root.to_string() ).collect::<Vec<_>>();
+
+        while let Some( lib_name ) = pending.pop() {
+            if let Some( lib ) = resolved.get( &lib_name ) {
+                if visited.insert( lib_name.clone() ) {
+                    dependents.entry( lib_name.clone() ).or_default();
+                    for dep in lib.dependencies.iter().filter( |dep| resolved.contains_key( *dep )) {
+                        dependents.entry( dep.clone() ).or_default().push( lib_name.clone() );
+                        pending.push( dep.clone() );
+                    }
+                }
+            }
+        }
+        // A library follows its dependents, as a dependency follows the libraries depending on it.
+        sort_topologically( &dependents )
+    }
+
+    /// Cargo directives for linking `roots` and their dependencies, in the order of
+    /// `link_order()`. A library linked by several libraries is linked after all of
+    /// them, while a search path is searched as early as it is first needed.
+    pub fn link_directives( &self, roots: &[&String] ) -> Vec<String> {
+        let resolved = self.resolved.borrow();
+        let directives = self
+            .link_order( roots )
+            .iter()
+            .flat_map( |lib_name| resolved[ lib_name ].link_directives.clone() )
+            .collect::<Vec<_>>();
+
+        let is_link_lib = |directive: &str| directive.starts_with( "rustc-link-lib=" );
+        let last_index = directives
+            .iter()
+            .enumerate()
+            .filter( |(_, directive)| is_link_lib( directive ))
+            .map( |(index, directive)| (directive, index) )
+            .collect::<HashMap<_,_>>();
+
+        let mut emitted = HashSet::<&String>::new();
+        directives
+            .iter()
+            .enumerate()
+            .filter( |&(index, directive)| if is_link_lib( directive ) {
+                last_index[ directive ] == index
+            } else {
+                emitted.insert( directive )
+            })
+            .map( |(_, directive)| directive.clone() )
+            .collect()
+    }
+
+    /// `roots` and all of their dependencies, including `header-dependencies`,
+    /// recursively, sorted topologically: each library follows its dependencies,
+    /// and libraries not depending on each other are sorted by name.
+    pub fn closure( &self, roots: &[&String] ) -> Vec<String> {
+        let resolved = self.resolved.borrow();
+        let mut edges = BTreeMap::<String,Vec<String>>::new();
+        let mut pending = roots.iter().map( |root| root.to_string() ).collect::<Vec<_>>();
+
+        while let Some( lib_name ) = pending.pop() {
+            if let (Some( lib ), Entry::Vacant( entry )) = (resolved.get( &lib_name ), edges.entry( lib_name )) {
+                let deps = lib.dependencies
+                    .iter()
+                    .chain( &lib.header_dependencies )
+                    .filter( |dep| resolved.contains_key( *dep ))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                pending.extend( deps.iter().cloned() );
+                entry.insert( deps );
+            }
+        }
+        sort_topologically( &edges )
+    }
+
+    /// `-I` and other clang args of the given libraries, in order and without duplicates.
+    pub fn clang_args_of<'a>( &self, lib_names: impl IntoIterator<Item=&'a String> ) -> Vec<String> {
+        let resolved = self.resolved.borrow();
+        let mut clang_args = Vec::<String>::new();
+        for lib in lib_names.into_iter().filter_map( |lib_name| resolved.get( lib_name )) {
+            let include_args = std::iter::once( &lib.include_dir )
+                .chain( &lib.include_paths )
+                .filter( |path| !path.is_empty() )
+                .map( |path| format!( "-I{}", path ));
+            for arg in include_args.chain( lib.clang_args.iter().cloned() ) {
+                if !clang_args.contains( &arg ) {
+                    clang_args.push( arg );
+                }
+            }
+        }
+        clang_args
+    }
+}
+
+/// The install prefixes to search the library in: the ones of its `exe` found in
+/// `PATH`, and on Unix, the standard ones too. Each comes with the executable found
+/// in it, if any. An executable not in any of `bindir` is recorded as `failure`.
+fn candidate_prefixes( spec: &Spec, failure: &mut Option<anyhow::Error>, tried: &mut Vec<String> ) -> Vec<(PathBuf,Option<PathBuf>)> {
+    let mut candidates = Vec::<(PathBuf,Option<PathBuf>)>::new();
+
+    for name in &spec.exe {
+        let Some( cmd_path ) = which( name ) else {
+            tried.push( format!( "{} not found in PATH", name ));
+            continue;
+        };
+        match search::prefix_of( &cmd_path, &spec.bindir ) {
+            Some( prefix ) => candidates.push(( prefix, Some( cmd_path ))),
+            None => {
+                let err = anyhow!( "{} is not in any `bindir` of {:?}", cmd_path.display(), spec.bindir );
+                tried.push( err.to_string() );
+                failure.get_or_insert( err );
+            },
+        }
+    }
+
+    if cfg!( unix ) {
+        for prefix in search::standard_prefixes() {
+            if !candidates.iter().any( |(candidate, _)| *candidate == prefix ) {
+                candidates.push(( prefix, None ));
+            }
+        }
+    }
+    candidates
+}
+
+/// The .pc files of the library, e.g. "tk86.pc or tk.pc".
+fn pc_file_names( pc_names: &[&str] ) -> String {
+    pc_names.iter().map( |name| format!( "{}.pc", name )).collect::<Vec<_>>().join( " or " )
+}
+
+/// The first one of `exe` found in any of `bindir` of the install prefix.
+fn exe_in_prefix( spec: &Spec, prefix: &Path ) -> Option<PathBuf> {
+    spec.bindir
+        .iter()
+        .flat_map( |bindir| spec.exe.iter().map( move |name| prefix.join( bindir ).join( name )))
+        .find( |exe| exe.exists() )
+}
+
+/// Locates the executable in `PATH`.
+fn which( name: &str ) -> Option<PathBuf> {
+    let output = Command::new( if cfg!(unix) { "which" } else { "where" })
+        .arg( name ).output().ok()?;
+    let s = std::str::from_utf8( output.stdout.as_slice() ).ok()?;
+    s.lines().next().map( |line| PathBuf::from( line.trim_end() ))
+}
+
+/// The library as told by its config script or config file found in `found_in`. The
+/// include dir is the first `-I` of the cflags, or else the one in `prefix`.
+fn resolved_by_flags( pkg_name: &str, spec: &Spec, flags: Flags, found_in: &str, prefix: Option<&Path>, statik: bool ) -> Result<Resolved> {
+    let include_paths = flags.cflags
+        .iter()
+        .filter_map( |flag| flag.strip_prefix( "-I" ))
+        .map( ToOwned::to_owned )
+        .collect::<Vec<_>>();
+    let include_dir = match (include_paths.first(), prefix) {
+        (Some( include_dir ), _) => include_dir.clone(),
+        (None, Some( prefix )) => search::include_dir( prefix, &spec.include_root, &spec.includedir )
+            .to_str()
+            .context( UTF8_PATH )?
+            .to_owned(),
+        (None, None) => String::new(),
+    };
+
+    Ok( Resolved {
+        found_in        : found_in.to_owned(),
+        version         : flags.version,
+        include_dir     ,
+        include_paths   ,
+        clang_args      : pkgconf::clang_args( flags.cflags ),
+        link_directives : pcfile::link_directives( pkg_name, &flags.libs, statik ),
+        ..Resolved::default()
+    })
+}
+
+/// Determines the version of a library located by searching, from the macro named
+/// `version-macro` in its headers, or from the output of its executable run with
+/// `version-args`.
+fn search_version( spec: &Spec, exe: Option<&Path>, include_dir: &str ) -> Option<String> {
+    if let Some( name ) = &spec.version_macro {
+        spec.headers
+            .iter()
+            .filter_map( |header| fs::read_to_string( Path::new( include_dir ).join( header )).ok() )
+            .find_map( |contents| contents.lines().find_map( |line| {
+                let mut words = line.trim_start().strip_prefix( '#' )?.split_whitespace();
+                (words.next() == Some( "define" ) && words.next() == Some( name ))
+                    .then( || words.next() )
+                    .flatten()
+                    .map( |value| value.trim_matches( '"' ).to_owned() )
+            }))
+    } else if let (Some( exe ), false) = (exe, spec.version_args.is_empty()) {
+        let output = Command::new( exe ).args( &spec.version_args ).output().ok()?;
+        version::find_version( &String::from_utf8_lossy( &output.stdout ))
+            .or_else( || version::find_version( &String::from_utf8_lossy( &output.stderr )))
+    } else {
+        None
+    }
+}
+
+/// Sorts the nodes of a graph without cycles, given the edges from each node to
+/// the nodes it depends on. Each node follows its dependencies, and nodes ready at
+/// the same time are sorted by name.
+fn sort_topologically( edges: &BTreeMap<String,Vec<String>> ) -> Vec<String> {
+    let mut dependents = BTreeMap::<&str,Vec<&str>>::new();
+    let mut unsorted_deps = BTreeMap::<&str,usize>::new();
+
+    for (node, deps) in edges {
+        let deps = deps.iter().collect::<BTreeSet<_>>();
+        unsorted_deps.insert( node, deps.len() );
+        for dep in deps {
+            dependents.entry( dep ).or_default().push( node );
+        }
+    }
+
+    let mut ready = unsorted_deps
+        .iter()
+        .filter( |(_, count)| **count == 0 )
+        .map( |(node, _)| *node )
+        .collect::<BTreeSet<_>>();
+    let mut sorted = Vec::with_capacity( edges.len() );
+
+    while let Some( node ) = ready.pop_first() {
+        sorted.push( node.to_owned() );
+        for dependent in dependents.get( node ).into_iter().flatten() {
+            let count = unsorted_deps.get_mut( dependent ).expect( "dependent should be a node." );
+            *count -= 1;
+            if *count == 0 {
+                ready.insert( dependent );
+            }
+        }
+    }
+    sorted
+}
+
+/// Checks the version of a library found in `found_in` against `version_req`, if any.
+fn check_version( pkg_name: &str, version_req: Option<&VersionReq>, version: Option<&str>, found_in: &str ) -> Result<()> {
+    match (version_req, version) {
+        (None, _) => Ok(()),
+        (Some( version_req ), Some( version )) => version_req.check( pkg_name, version, found_in ),
+        (Some( version_req ), None) => {
+            version::reject();
+            Err( anyhow!( "failed to determine the version of {} found in {}, which is required to be `{}`. \
+                Try specifying `version-macro` or `version-args`.", pkg_name, found_in, version_req ))
+        },
+    }
+}
+
+/// Links one library file of each group found in `lib_dirs`, preferring static
+/// archives if `statik`, and searches the directories of these files.
+fn link_directives_for_libs<'a>( lib_dirs: &[PathBuf], lib_groups: impl IntoIterator<Item=&'a LibGroup>, statik: bool ) -> Result<Vec<String>> {
+    let mut search_dirs = Vec::new();
+    let mut link_libs = Vec::new();
+
+    for lib_names in lib_groups {
+        let (lib_dir, lib_name) = lib_dirs
+            .iter()
+            .flat_map( |lib_dir| lib_names
+                .iter()
+                .filter( |lib_name| lib_dir.join( lib_name ).exists() )
+                .map( move |lib_name| (lib_dir, lib_name) ))
+            .min_by_key( |(_, lib_name)| statik && !lib_name.ends_with( ".a" ))
+            .ok_or_else( || anyhow!( "none of {} found in {:?}", lib_names.join( ", " ), lib_dirs ))?;
+
+        let search_dir = format!( "rustc-link-search=native={}", lib_dir.to_str().context( UTF8_PATH )? );
+        if !search_dirs.contains( &search_dir ) {
+            search_dirs.push( search_dir );
+        }
+        link_libs.push( if statik && lib_name.ends_with( ".a" ) {
+            format!( "rustc-link-lib=static={}", get_link_name( lib_name ))
+        } else {
+            format!( "rustc-link-lib={}", get_link_name( lib_name ))
+        });
+    }
+
+    search_dirs.extend( link_libs );
+    Ok( search_dirs )
+}
+
+fn get_link_name( lib_name: &str ) -> &str {
+    let start = if lib_name.starts_with( "lib" ) { 3 } else { 0 };
+    match lib_name.rfind('.') {
+        Some( dot ) => &lib_name[ start..dot ],
+        None => &lib_name[ start.. ],
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+    use crate::spec::Metadata;
+
+    /// Lays out a vendored prefix of the library in `dir`, with its version in `<name>.h`.
+    fn vendor( dir: &Path, name: &str, lib_file: &str, version: &str ) -> PathBuf {
+        let prefix = dir.join( name );
+        fs::create_dir_all( prefix.join( "include" )).unwrap();
+        fs::create_dir_all( prefix.join( "lib" )).unwrap();
+        fs::write( prefix.join( "include" ).join( format!( "{}.h", name )),
+            format!( "#define {}_VERSION \"{}\"\n", name.to_uppercase(), version )).unwrap();
+        fs::write( prefix.join( "lib" ).join( lib_file ), "" ).unwrap();
+        prefix
+    }
+
+    fn lib_info( manifest: &str ) -> LibInfo {
+        let package = inwelling::Package {
+            name     : "downstream".to_owned(),
+            manifest : PathBuf::from( "/downstream/Cargo.toml" ),
+            metadata : toml::from_str( manifest ).unwrap(),
+            rs_paths : None,
+        };
+        let metadata = Metadata::parse( &package ).unwrap();
+        let builds = metadata.build.into_iter().map( |build| build.name ).collect();
+        LibInfo::new( metadata.specs.into_iter().collect(), builds, None )
+    }
+
+    #[test]
+    fn dedups_link_directives() {
+        let lib_info = lib_info( "" );
+        let directives = |directives: &[&str]| directives.iter().map( |directive| directive.to_string() ).collect::<Vec<_>>();
+        lib_info.resolved.borrow_mut().insert( "tk".to_owned(), Resolved {
+            link_directives : directives( &[ "rustc-link-search=native=/usr/lib", "rustc-link-lib=tk", "rustc-link-lib=z" ]),
+            dependencies    : vec![ "tcl".to_owned() ],
+            ..Resolved::default()
+        });
+        lib_info.resolved.borrow_mut().insert( "tcl".to_owned(), Resolved {
+            link_directives : directives( &[ "rustc-link-search=native=/opt/tcl/lib", "rustc-link-search=native=/usr/lib",
+                "rustc-link-lib=tcl", "rustc-link-lib=z" ]),
+            ..Resolved::default()
+        });
+
+        // A library is linked at its last occurrence, a search path is searched at its first.
+        assert_eq!( lib_info.link_directives( &[ &"tk".to_owned() ]), directives( &[
+            "rustc-link-search=native=/usr/lib",
+            "rustc-link-lib=tk",
+            "rustc-link-search=native=/opt/tcl/lib",
+            "rustc-link-lib=tcl",
+            "rustc-link-lib=z",
+        ]));
+    }
+
+    #[test]
+    fn links_libs_from_the_first_dir_having_them() {
+        let dir = env::temp_dir().join( format!( "clib-test-link-{}", std::process::id() ));
+        let (lib, lib64) = (dir.join( "lib" ), dir.join( "lib64" ));
+        fs::create_dir_all( &lib ).unwrap();
+        fs::create_dir_all( &lib64 ).unwrap();
+        for file in [ lib.join( "libfoo.so" ), lib.join( "libbar.so" ), lib.join( "libbar.a" ), lib64.join( "libbaz.so" )] {
+            fs::write( file, "" ).unwrap();
+        }
+        let lib_groups = [ "libfoo.so", "libbar.so libbar.a", "libbaz.so" ]
+            .map( |names| names.split( ' ' ).map( ToOwned::to_owned ).collect::<Vec<_>>() );
+        let lib_dirs = [ lib.clone(), lib64.clone() ];
+
+        let search = |dir: &Path| format!( "rustc-link-search=native={}", dir.display() );
+        assert_eq!( link_directives_for_libs( &lib_dirs, &lib_groups, false ).unwrap(), [
+            search( &lib ),
+            search( &lib64 ),
+            "rustc-link-lib=foo".to_owned(),
+            "rustc-link-lib=bar".to_owned(),
+            "rustc-link-lib=baz".to_owned(),
+        ]);
+        // Static archives are preferred if static linking is asked for.
+        assert_eq!( link_directives_for_libs( &lib_dirs, &lib_groups, true ).unwrap()[ 3 ], "rustc-link-lib=static=bar" );
+        assert!( link_directives_for_libs( &lib_dirs[ ..1 ], &lib_groups, false ).is_err() );
+
+        fs::remove_dir_all( dir ).unwrap();
+    }
+
+    #[test]
+    fn sorts_topologically_and_by_name() {
+        let edges = |edges: &[(&str, &[&str])]| edges
+            .iter()
+            .map( |(node, deps)| (node.to_string(), deps.iter().map( |dep| dep.to_string() ).collect()) )
+            .collect::<BTreeMap<_,_>>();
+
+        // Libraries not depending on each other are sorted by name.
+        let sorted = sort_topologically( &edges( &[
+            ("zlib" , &[]),
+            ("expat", &[]),
+            ("png"  , &["zlib"]),
+            ("tk"   , &["tcl", "x11"]),
+            ("tcl"  , &["zlib"]),
+            ("x11"  , &[]),
+        ]));
+        assert_eq!( sorted, [ "expat", "x11", "zlib", "png", "tcl", "tk" ]);
+        assert_eq!( sort_topologically( &edges( &[ ("b", &["a", "a"]), ("a", &[]) ])), [ "a", "b" ]);
+
+        // Libraries in a cycle, and those depending on them, are left out.
+        assert_eq!( sort_topologically( &edges( &[ ("a", &["b"]), ("b", &["a"]), ("c", &["a"]), ("d", &[]) ])), [ "d" ]);
+    }
+
+    #[test]
+    fn failure_for_a_version_requirement_is_not_cached_for_others() {
+        let dir = env::temp_dir().join( format!( "clib-test-probe-{}", std::process::id() ));
+        let zlib = vendor( &dir, "zlib", "libz.a", "1.2.13" );
+        let expat = vendor( &dir, "expat", "libexpat.a", "2.5.0" );
+
+        let lib_info = lib_info( &format!( r#"
+            build = [ "expat", "zlib" ]
+
+            [spec.zlib]
+            probe = [ "vendored" ]
+            vendored = {:?}
+            headers = [ "zlib.h" ]
+            version-macro = "ZLIB_VERSION"
+            libs = [ "libz.a" ]
+
+            [spec.expat]
+            probe = [ "vendored" ]
+            vendored = {:?}
+            headers = [ "expat.h" ]
+            libs = [ "libexpat.a" ]
+            dependencies.zlib = {{ version = ">=99" }}
+        "#, zlib.to_str().unwrap(), expat.to_str().unwrap() ));
+
+        assert!( lib_info.probe( "expat" ).is_err() );
+        lib_info.probe( "zlib" ).unwrap();
+        assert_eq!( lib_info.resolved.borrow()[ "zlib" ].version.as_deref(), Some( "1.2.13" ));
+
+        // Once resolved, zlib still fails the requirement of expat.
+        assert!( lib_info.probe( "expat" ).is_err() );
+        let report = lib_info.failure_report( &("expat".to_owned(), None) );
+        assert!( report.iter().any( |line| line.contains( "fails to probe library zlib `>=99`" )), "{:#?}", report );
+
+        fs::remove_dir_all( dir ).unwrap();
+    }
+
+    #[test]
+    fn failure_to_find_a_library_is_cached_for_every_version_requirement() {
+        let dir = env::temp_dir().join( format!( "clib-test-not-found-{}", std::process::id() ));
+        let expat = vendor( &dir, "expat", "libexpat.a", "2.5.0" );
+        let png = vendor( &dir, "png", "libpng.a", "1.6.40" );
+
+        let lib_info = lib_info( &format!( r#"
+            build = [ "expat", "png" ]
+
+            [spec.zlib]
+            probe = [ "vendored" ]
+            vendored = {:?}
+            headers = [ "zlib.h" ]
+            libs = [ "libz.a" ]
+
+            [spec.expat]
+            probe = [ "vendored" ]
+            vendored = {:?}
+            headers = [ "expat.h" ]
+            libs = [ "libexpat.a" ]
+            dependencies.zlib = {{ version = ">=1.2" }}
+
+            [spec.png]
+            probe = [ "vendored" ]
+            vendored = {:?}
+            headers = [ "png.h" ]
+            libs = [ "libpng.a" ]
+            dependencies.zlib = {{ version = ">=1.3" }}
+        "#, dir.join( "zlib" ).to_str().unwrap(), expat.to_str().unwrap(), png.to_str().unwrap() ));
+
+        assert!( lib_info.probe( "expat" ).is_err() );
+        assert!( lib_info.probe( "png" ).is_err() );
+        let probed = lib_info.attempts.borrow().keys().filter( |(name, _)| name == "zlib" ).cloned().collect::<Vec<_>>();
+        assert_eq!( probed, [ ("zlib".to_owned(), Some( ">=1.2".to_owned() )) ]);
+
+        // The strategies tried for the first requirement are reported for the second one.
+        let report = lib_info.failure_report( &("png".to_owned(), None) );
+        assert!( report.iter().any( |line| line.contains( "fails to probe library zlib `>=1.3`" )), "{:#?}", report );
+        assert!( report.iter().any( |line| line.contains( "vendored prefix" ) && line.contains( "not found" )), "{:#?}", report );
+
+        fs::remove_dir_all( dir ).unwrap();
+    }
+
+    #[test]
+    fn dependency_cycle_fails_docs() {
+        let dir = env::temp_dir().join( format!( "clib-test-docs-{}", std::process::id() ));
+        let tcl = vendor( &dir, "tcl", "libtcl.a", "8.6.13" ).join( "include" );
+        let tk = vendor( &dir, "tk", "libtk.a", "8.6.13" ).join( "include" );
+
+        let lib_info = lib_info( &format!( r#"
+            build = [ "tk" ]
+
+            [spec.tcl]
+            bundled-include-dir = {:?}
+            headers = [ "tcl.h" ]
+            dependencies = [ "tk" ]
+
+            [spec.tk]
+            bundled-include-dir = {:?}
+            headers = [ "tk.h" ]
+            dependencies = [ "tcl" ]
+        "#, tcl.to_str().unwrap(), tk.to_str().unwrap() ));
+
+        let err = lib_info.resolve_for_docs( "tk" ).unwrap_err();
+        assert_eq!( err.to_string(), "dependency cycle detected: tk -> tcl -> tk" );
+        assert!( lib_info.resolved.borrow().is_empty() );
+
+        fs::remove_dir_all( dir ).unwrap();
+    }
+}
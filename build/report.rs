@@ -0,0 +1,214 @@
+//! A record of what has been resolved, written to `OUT_DIR/clib-report.json` for
+//! diagnosing builds, e.g. from the artifacts of CI.
+
+use crate::{
+    probe::{LibInfo, Outcome, ProbeKey},
+    spec::Origin,
+};
+
+use anyhow::{Context, Result};
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    fs,
+    path::Path,
+    rc::Rc,
+    time::Duration,
+};
+
+thread_local! {
+    static DIRECTIVES: RefCell<Vec<String>> = RefCell::default();
+}
+
+/// Prints the cargo directive, e.g. "rustc-link-lib=tk8.6", recording it for the report.
+pub fn emit( directive: String ) {
+    println!( "cargo:{}", directive );
+    DIRECTIVES.with( |directives| directives.borrow_mut().push( directive ));
+}
+
+/// Everything in the report, besides the directives emitted so far.
+pub struct Report<'a> {
+    pub builds     : &'a BTreeMap<String,Rc<Origin>>,
+    pub lib_info   : &'a LibInfo,
+    pub clang_args : &'a [String],
+    pub bindgen    : Option<Duration>, // none if bindgen has not run
+}
+
+/// Just enough JSON for the report.
+enum Json {
+    Null,
+    Bool( bool ),
+    Number( f64 ),
+    String( String ),
+    Array( Vec<Json> ),
+    Object( Vec<(String,Json)> ),
+}
+
+impl Json {
+    fn string( s: impl Into<String> ) -> Json {
+        Json::String( s.into() )
+    }
+
+    fn strings<'a>( strings: impl IntoIterator<Item=&'a String> ) -> Json {
+        Json::Array( strings.into_iter().map( |s| Json::string( s.clone() )).collect() )
+    }
+
+    fn millis( duration: Duration ) -> Json {
+        Json::Number( duration.as_secs_f64() * 1000.0 )
+    }
+
+    fn object<'a>( fields: impl IntoIterator<Item=(&'a str, Json)> ) -> Json {
+        Json::Object( fields.into_iter().map( |(key, value)| (key.to_owned(), value) ).collect() )
+    }
+
+    fn write( &self, out: &mut String, indent: usize ) {
+        let pad = |out: &mut String, indent: usize| out.push_str( &"  ".repeat( indent ));
+        match self {
+            Json::Null => out.push_str( "null" ),
+            Json::Bool( b ) => write!( out, "{}", b ).expect( "writing to String" ),
+            Json::Number( n ) => write!( out, "{:.3}", n ).expect( "writing to String" ),
+            Json::String( s ) => write_string( out, s ),
+            Json::Array( items ) if items.is_empty() => out.push_str( "[]" ),
+            Json::Array( items ) => {
+                out.push_str( "[\n" );
+                for (index, item) in items.iter().enumerate() {
+                    pad( out, indent+1 );
+                    item.write( out, indent+1 );
+                    out.push_str( if index+1 < items.len() { ",\n" } else { "\n" });
+                }
+                pad( out, indent );
+                out.push( ']' );
+            },
+            Json::Object( fields ) if fields.is_empty() => out.push_str( "{}" ),
+            Json::Object( fields ) => {
+                out.push_str( "{\n" );
+                for (index, (key, value)) in fields.iter().enumerate() {
+                    pad( out, indent+1 );
+                    write_string( out, key );
+                    out.push_str( ": " );
+                    value.write( out, indent+1 );
+                    out.push_str( if index+1 < fields.len() { ",\n" } else { "\n" });
+                }
+                pad( out, indent );
+                out.push( '}' );
+            },
+        }
+    }
+}
+
+fn write_string( out: &mut String, s: &str ) {
+    out.push( '"' );
+    for c in s.chars() {
+        match c {
+            '"'  => out.push_str( "\\\"" ),
+            '\\' => out.push_str( "\\\\" ),
+            '\n' => out.push_str( "\\n" ),
+            '\r' => out.push_str( "\\r" ),
+            '\t' => out.push_str( "\\t" ),
+            c if (c as u32) < 0x20 => write!( out, "\\u{:04x}", c as u32 ).expect( "writing to String" ),
+            c => out.push( c ),
+        }
+    }
+    out.push( '"' );
+}
+
+impl Report<'_> {
+    /// Writes the report to `OUT_DIR/clib-report.json`, replacing the previous one.
+    pub fn write( &self, out_dir: &Path ) -> Result<()> {
+        let path = out_dir.join( "clib-report.json" );
+        let mut json = String::new();
+        self.to_json().write( &mut json, 0 );
+        json.push( '\n' );
+        fs::write( &path, json ).with_context( || format!( "failed to write {:?}", path ))
+    }
+
+    fn to_json( &self ) -> Json {
+        let resolved = self.lib_info.resolved.borrow();
+        let failed = self.lib_info.failed.borrow();
+        let attempts = self.lib_info.attempts.borrow();
+
+        let builds = self.builds.iter().map( |(name, origin)| Json::object([
+            ( "name"    , Json::string( name )),
+            ( "package" , Json::string( &origin.package )),
+            ( "manifest", Json::string( origin.manifest.display().to_string() )),
+            ( "probed"  , Json::Bool( resolved.contains_key( name ))),
+        ])).collect();
+
+        let probed = self.builds.keys().filter( |name| resolved.contains_key( *name )).collect::<Vec<_>>();
+        let link_order = self.lib_info.link_order( &probed );
+
+        let lib_names = attempts.keys().chain( failed.keys() ).map( |(name, _)| name ).chain( resolved.keys() ).collect::<BTreeSet<_>>();
+        let mut probe_timings = Vec::new();
+        let libraries = lib_names.into_iter().map( |name| {
+            // A library may be probed once more for each version requirement of its dependents.
+            let of_lib = |key: &&ProbeKey| key.0 == *name;
+            let mut probes = attempts.iter().filter( |(key, _)| of_lib( key )).collect::<Vec<_>>();
+            probes.sort_by_key( |(key, _)| *key );
+            let attempts = probes
+                .into_iter()
+                .flat_map( |(key, attempts)| attempts.iter().map( move |attempt| (&key.1, attempt) ))
+                .collect::<Vec<_>>();
+            let failures = failed
+                .iter()
+                .filter( |(key, _)| of_lib( key ))
+                .map( |(key, err)| (&key.1, err) )
+                .collect::<BTreeSet<_>>();
+            let elapsed = attempts.iter().map( |(_, attempt)| attempt.elapsed ).sum::<Duration>();
+            probe_timings.push(( name.clone(), Json::millis( elapsed )));
+
+            let mut fields = Vec::<(&str,Json)>::new();
+            match resolved.get( name ) {
+                Some( lib ) => fields.extend([
+                    ( "status"             , Json::string( "resolved" )),
+                    ( "found_by"           , Json::string( &lib.found_by )),
+                    ( "found_in"           , Json::string( &lib.found_in )),
+                    ( "linked_statically"  , Json::Bool( link_order.contains( name ) && lib.links_static_archive() )),
+                    ( "pc_name"            , lib.pc_name.as_ref().map_or( Json::Null, Json::string )),
+                    ( "version"            , lib.version.as_ref().map_or( Json::Null, Json::string )),
+                    ( "include_dir"        , Json::string( &lib.include_dir )),
+                    ( "include_paths"      , Json::strings( &lib.include_paths )),
+                    ( "headers"            , Json::strings( &lib.headers )),
+                    ( "clang_args"         , Json::strings( &lib.clang_args )),
+                    ( "link_directives"    , Json::strings( &lib.link_directives )),
+                    ( "dependencies"       , Json::strings( &lib.dependencies )),
+                    ( "header_dependencies", Json::strings( &lib.header_dependencies )),
+                ]),
+                None => fields.push(( "status", Json::string( "failed" ))),
+            }
+            fields.push(( "failures", Json::Array( failures.iter().map( |(version_req, err)| Json::object([
+                ( "version_req", version_req.as_ref().map_or( Json::Null, Json::string )),
+                ( "error"      , Json::string( *err )),
+            ])).collect() )));
+            fields.push(( "attempts", Json::Array( attempts.iter().map( |(version_req, attempt)| {
+                let (outcome, message) = match &attempt.outcome {
+                    Outcome::Skipped( reason ) => ("skipped", Json::string( reason )),
+                    Outcome::Failed( err ) => ("failed", Json::string( err )),
+                    Outcome::Succeeded => ("succeeded", Json::Null),
+                };
+                Json::object([
+                    ( "version_req", version_req.as_ref().map_or( Json::Null, Json::string )),
+                    ( "strategy"   , Json::string( &attempt.strategy )),
+                    ( "outcome"    , Json::string( outcome )),
+                    ( "message"    , message ),
+                    ( "tried"      , Json::strings( &attempt.tried )),
+                    ( "millis"     , Json::millis( attempt.elapsed )),
+                ])
+            }).collect() )));
+            (name.clone(), Json::object( fields ))
+        }).collect::<Vec<_>>();
+
+        Json::object([
+            ( "builds"    , Json::Array( builds )),
+            ( "libraries" , Json::Object( libraries )),
+            ( "link_order", Json::strings( &link_order )),
+            ( "directives", DIRECTIVES.with( |directives| Json::strings( directives.borrow().iter() ))),
+            ( "clang_args", Json::strings( self.clang_args )),
+            ( "timings"   , Json::object([
+                ( "probe_millis"  , Json::Object( probe_timings )),
+                ( "bindgen_millis", self.bindgen.map_or( Json::Null, Json::millis )),
+            ])),
+        ])
+    }
+}
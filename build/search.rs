@@ -0,0 +1,182 @@
+//! Install prefixes and their directories, for searching libraries without pkg-config.
+
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Install prefixes searched on Unix, besides the ones of executables found in
+/// `PATH`: /usr/local, /usr and each directory in /opt.
+pub fn standard_prefixes() -> Vec<PathBuf> {
+    let mut prefixes = vec![ PathBuf::from( "/usr/local" ), PathBuf::from( "/usr" )];
+
+    if let Ok( entries ) = fs::read_dir( "/opt" ) {
+        let mut opt_dirs = entries
+            .filter_map( |entry| entry.ok() )
+            .map( |entry| entry.path() )
+            .filter( |path| path.is_dir() )
+            .collect::<Vec<_>>();
+        opt_dirs.sort();
+        prefixes.extend( opt_dirs );
+    }
+    prefixes
+}
+
+/// Default `bindir`: executables are in "bin" of the install prefix.
+pub const BINDIR: &[&str] = &[ "bin" ];
+
+/// Default `libdir`: the multiarch directory of the target, "lib64" and "lib".
+pub const LIBDIR: &[&str] = &[ "lib/<triple>", "lib64", "lib" ];
+
+/// Default `include-root`: headers are in "include" of the install prefix.
+pub const INCLUDE_ROOT: &[&str] = &[ "include" ];
+
+pub fn default_dirs( dirs: &[&str] ) -> Vec<String> {
+    dirs.iter().map( |dir| dir.to_string() ).collect()
+}
+
+/// The install prefix of the executable, which should be in one of `bindir`.
+pub fn prefix_of( exe: &Path, bindir: &[String] ) -> Option<PathBuf> {
+    let parent = exe.parent()?;
+    bindir.iter().find_map( |bindir| {
+        let mut prefix = parent;
+        for component in Path::new( bindir ).components().rev() {
+            if prefix.file_name()? != component.as_os_str() {
+                return None;
+            }
+            prefix = prefix.parent()?;
+        }
+        Some( prefix.to_owned() )
+    })
+}
+
+/// Existing directories of libraries in `prefix`, in the order of `libdir`, where
+/// "<triple>" stands for the multiarch tuple of the target, e.g. "x86_64-linux-gnu".
+pub fn lib_dirs( prefix: &Path, libdir: &[String] ) -> Vec<PathBuf> {
+    libdir
+        .iter()
+        .filter_map( |dir| expand_triple( dir ))
+        .map( |dir| prefix.join( dir ))
+        .filter( |dir| dir.is_dir() )
+        .collect()
+}
+
+/// The include directory in `prefix`: the first existing one of `includedir` in
+/// any of `include_root`, or else the first existing root.
+pub fn include_dir( prefix: &Path, include_root: &[String], includedir: &[String] ) -> PathBuf {
+    let roots = include_root.iter().map( |root| prefix.join( root )).collect::<Vec<_>>();
+    roots
+        .iter()
+        .flat_map( |root| includedir.iter().map( move |dir| root.join( dir )))
+        .find( |dir| dir.exists() )
+        .or_else( || roots.iter().find( |root| root.exists() ).cloned() )
+        .unwrap_or_else( || prefix.join( "include" ))
+}
+
+/// Replaces "<triple>" in `dir` with the multiarch tuple of the target, if known.
+pub fn expand_triple( dir: &str ) -> Option<String> {
+    match multiarch() {
+        Some( multiarch ) => Some( dir.replace( "<triple>", &multiarch )),
+        None => Some( dir.to_owned() ).filter( |dir| !dir.contains( "<triple>" )),
+    }
+}
+
+/// The Debian multiarch tuple of the target being built for.
+fn multiarch() -> Option<String> {
+    multiarch_of( &env::var( "TARGET" ).ok()? )
+}
+
+/// The Debian multiarch tuple of `target`, e.g. "x86_64-linux-gnu" for
+/// "x86_64-unknown-linux-gnu".
+fn multiarch_of( target: &str ) -> Option<String> {
+    let parts = target.split( '-' ).collect::<Vec<_>>();
+    let (arch, os_env) = match parts.as_slice() {
+        [ arch, _vendor, os, env ] => (*arch, format!( "{}-{}", os, env )),
+        [ arch, _vendor, os ] => (*arch, os.to_string()),
+        _ => return None,
+    };
+    let arch = match arch {
+        "i586" | "i686" => "i386",
+        arch if arch.starts_with( "armv" ) => "arm",
+        arch => arch,
+    };
+    Some( format!( "{}-{}", arch, os_env ))
+}
+
+/// Guesses the headers of a library without spec, relative to its include dir:
+/// "<name>.h" or "<name>/<name>.h" for any of `names` with or without "lib" prefix
+/// and version suffix, or else every header in an include dir of its own, i.e.
+/// not named "include".
+pub fn guess_headers( include_dir: &Path, names: &[&str] ) -> Vec<String> {
+    let mut stems = Vec::<String>::new();
+    for name in names {
+        let name = name.to_lowercase();
+        let short = name.strip_prefix( "lib" ).unwrap_or( &name ).to_owned();
+        let base = short.trim_end_matches( |c: char| c.is_ascii_digit() || ".-_".contains( c )).to_owned();
+        for stem in [ name, short, base ] {
+            if !stem.is_empty() && !stems.contains( &stem ) {
+                stems.push( stem );
+            }
+        }
+    }
+
+    let found = stems
+        .iter()
+        .flat_map( |stem| [ format!( "{}.h", stem ), format!( "{0}/{0}.h", stem )])
+        .find( |header| include_dir.join( header ).is_file() );
+    if let Some( header ) = found {
+        return vec![ header ];
+    }
+
+    if include_dir.file_name().is_some_and( |name| name == "include" ) {
+        return Vec::new();
+    }
+    let mut headers = fs::read_dir( include_dir )
+        .into_iter()
+        .flatten()
+        .filter_map( |entry| entry.ok() )
+        .filter_map( |entry| entry.file_name().into_string().ok() )
+        .filter( |name| name.ends_with( ".h" ))
+        .collect::<Vec<_>>();
+    headers.sort();
+    headers
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_prefixes_of_executables() {
+        let bindir = |dirs: &[&str]| dirs.iter().map( |dir| dir.to_string() ).collect::<Vec<_>>();
+        for (exe, dirs, prefix) in [
+            ("/usr/bin/tclsh"        , &[ "bin" ][..]        , Some( "/usr" )),
+            ("/opt/tcl/bin/x64/tclsh", &[ "bin" ]            , None ),
+            ("/opt/tcl/bin/x64/tclsh", &[ "bin", "bin/x64" ] , Some( "/opt/tcl" )),
+            ("/usr/local/sbin/tclsh" , &[ "bin" ]            , None ),
+            ("tclsh"                 , &[ "bin" ]            , None ),
+        ] {
+            assert_eq!( prefix_of( Path::new( exe ), &bindir( dirs )).as_deref(), prefix.map( Path::new ), "{}", exe );
+        }
+    }
+
+    #[test]
+    fn expands_triples() {
+        for (target, multiarch) in [
+            ("x86_64-unknown-linux-gnu"     , Some( "x86_64-linux-gnu" )),
+            ("i686-unknown-linux-gnu"       , Some( "i386-linux-gnu" )),
+            ("armv7-unknown-linux-gnueabihf", Some( "arm-linux-gnueabihf" )),
+            ("aarch64-unknown-linux-musl"   , Some( "aarch64-linux-musl" )),
+            ("x86_64-apple-darwin"          , Some( "x86_64-darwin" )),
+            ("wasm32"                       , None ),
+        ] {
+            assert_eq!( multiarch_of( target ).as_deref(), multiarch, "{}", target );
+        }
+
+        // Dirs of the tuple are left out if it is unknown, e.g. without `TARGET`.
+        let expected = multiarch().map( |multiarch| format!( "lib/{}", multiarch ));
+        assert_eq!( expand_triple( "lib/<triple>" ), expected );
+        assert_eq!( expand_triple( "lib64" ).as_deref(), Some( "lib64" ));
+    }
+}
@@ -0,0 +1,828 @@
+//! Typed schema of the `[package.metadata.inwelling.clib]` sections collected
+//! from downstream manifests.
+//!
+//! Every value is checked while decoding: unknown keys are reported with a
+//! "did you mean" hint, and type mismatches name the contributing package, its
+//! manifest and the full key path.
+
+use crate::{search, version::VersionReq};
+
+use anyhow::{Result, anyhow};
+
+use regex::Regex;
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display},
+    ops::Deref,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+type Toml = toml::value::Value;
+
+/// Key path of the metadata section in a downstream manifest.
+const ROOT_KEY: &str = "package.metadata.inwelling.clib";
+
+const METADATA_KEYS: &[&str] = &[ "build", "modules", "spec" ];
+
+const BUILD_KEYS: &[&str] = &[ "name", "headers", "optional" ];
+
+const SPEC_KEYS: &[&str] = &[
+    "required",
+    "pc-alias",
+    "headers",
+    "dependencies",
+    "header-dependencies",
+    "config-script",
+    "config-file",
+    "cmake",
+    "vendored",
+    "probe",
+    "exe",
+    "bindir",
+    "libdir",
+    "include-root",
+    "includedir",
+    "libs",
+    "libs-private",
+    "for-docs-rs",
+    "bundled-include-dir",
+    "bindgen",
+    "version",
+    "version-macro",
+    "version-args",
+    "version-cfgs",
+];
+
+const CONFIG_FILE_KEYS: &[&str] = &[ "name", "cflags", "libs", "libs-private", "version" ];
+
+/// Strategies of probing a library, in the default order.
+pub const PROBE_STRATEGIES: &[&str] = &[
+    "env",
+    "pkg-config",
+    "cmake",
+    "config-script",
+    "compiler",
+    "search",
+    "vendored",
+];
+
+const CMAKE_KEYS: &[&str] = &[ "package", "components", "targets" ];
+
+const BINDGEN_KEYS: &[&str] = &[
+    "allowlist-function",
+    "allowlist-type",
+    "allowlist-var",
+    "allowlist-path",
+    "blocklist-function",
+    "blocklist-type",
+    "blocklist-var",
+    "opaque-type",
+    "enum-style",
+    "derive",
+    "size_t-is-usize",
+    "layout-tests",
+    "doc-comments",
+];
+
+/// Values accepted by bindgen's `EnumVariation`.
+const ENUM_STYLES: &[&str] = &[
+    "rust", "rust_non_exhaustive", "bitfield", "consts", "moduleconsts", "newtype", "newtype_global",
+];
+
+const DERIVES: &[&str] = &[
+    "Debug", "Default", "Copy", "Hash", "PartialEq", "Eq", "PartialOrd", "Ord",
+];
+
+const DEPENDENCY_KEYS: &[&str] = &[ "os", "version" ];
+
+const OS_NAMES: &[&str] = &[
+    "android", "dragonfly", "freebsd", "ios", "linux", "macos", "netbsd", "openbsd", "windows", "unix",
+];
+
+/// The downstream package which contributed some metadata.
+#[derive( Debug )]
+pub struct Origin {
+    pub package  : String,
+    pub manifest : PathBuf,
+}
+
+/// Malformed metadata, located by package, manifest and key path.
+#[derive( Debug )]
+pub struct SpecError {
+    pub package  : String,
+    pub manifest : PathBuf,
+    pub key      : String,
+    pub message  : String,
+}
+
+impl Display for SpecError {
+    fn fmt( &self, f: &mut fmt::Formatter ) -> fmt::Result {
+        write!( f, "invalid clib metadata in package `{}` ({}), at `{}`: {}",
+            self.package, self.manifest.display(), self.key, self.message )
+    }
+}
+
+impl Error for SpecError {}
+
+/// Contents of `[package.metadata.inwelling.clib]` of one downstream package.
+#[derive( Debug )]
+pub struct Metadata {
+    pub origin  : Rc<Origin>,
+    pub build   : Vec<Build>,
+    pub modules : bool,
+    pub specs   : BTreeMap<String,Spec>,
+}
+
+/// A library in `build`, e.g. `"tk86"` or `{ name = "tk86", optional = true }`.
+#[derive( Debug )]
+pub struct Build {
+    pub name     : String,
+    pub optional : bool, // not failing the build even if `CLIB_STRICT` is set
+}
+
+/// Contents of `[package.metadata.inwelling.clib.spec.<name>]`.
+#[derive( Debug, PartialEq )]
+pub struct Spec {
+    pub required            : bool, // failing the build if the library fails to be probed
+    pub pc_alias            : Vec<String>,
+    pub headers             : Vec<String>,
+    pub dependencies        : Vec<Dependency>,
+    pub header_dependencies : Vec<Dependency>,
+    pub config_script       : Vec<String>,
+    pub config_file         : Option<ConfigFile>,
+    pub cmake               : Option<CMakePackage>,
+    pub vendored            : Option<ManifestPath>,
+    pub probe               : Option<Vec<String>>,
+    pub exe                 : Vec<String>,
+    pub bindir              : Vec<String>,
+    pub libdir              : Vec<String>,
+    pub include_root        : Vec<String>,
+    pub includedir          : Vec<String>,
+    pub libs                : Vec<LibGroup>,
+    pub libs_private        : Vec<LibGroup>,
+    pub for_docs_rs         : Option<ManifestPath>,
+    pub bundled_include_dir : Option<ManifestPath>, // headers shipped for docs-only builds
+    pub bindgen             : BindgenOptions,
+    pub version             : Option<VersionReq>,
+    pub version_macro       : Option<String>,
+    pub version_args        : Vec<String>,
+    pub version_cfgs        : Vec<String>, // e.g. "8.6" for `clib_tcl86_ge_8_6`
+}
+
+/// A path in the spec relative to the manifest of the package specifying it. Specs
+/// of packages in different dirs are the same if these paths are written the same,
+/// so the first package by name specifying it provides the files.
+#[derive( Debug )]
+pub struct ManifestPath {
+    pub value : String, // as written in the manifest
+    pub path  : PathBuf,
+}
+
+impl ManifestPath {
+    fn new( value: String, origin: &Origin ) -> Self {
+        let path = origin.manifest.parent().expect( "the manifest dir" ).join( &value );
+        ManifestPath{ value, path }
+    }
+}
+
+impl PartialEq for ManifestPath {
+    fn eq( &self, other: &Self ) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Deref for ManifestPath {
+    type Target = Path;
+
+    fn deref( &self ) -> &Path {
+        &self.path
+    }
+}
+
+/// Contents of `[package.metadata.inwelling.clib.spec.<name>.config-file]`: a shell
+/// script defining variables, e.g. tclConfig.sh, and which variables to use.
+#[derive( Debug, PartialEq )]
+pub struct ConfigFile {
+    pub name         : Vec<String>,
+    pub cflags       : Vec<String>,
+    pub libs         : Vec<String>,
+    pub libs_private : Vec<String>,
+    pub version      : Vec<String>,
+}
+
+/// Contents of `[package.metadata.inwelling.clib.spec.<name>.cmake]`: the package
+/// to find by `find_package()`, and its imported targets to use.
+#[derive( Debug, PartialEq )]
+pub struct CMakePackage {
+    pub package    : String,
+    pub components : Vec<String>,
+    pub targets    : Vec<String>,
+}
+
+/// Contents of `[package.metadata.inwelling.clib.spec.<name>.bindgen]`.
+#[derive( Clone, Debug, Default, PartialEq )]
+pub struct BindgenOptions {
+    pub allowlist_function : Vec<String>,
+    pub allowlist_type     : Vec<String>,
+    pub allowlist_var      : Vec<String>,
+    pub allowlist_path     : Vec<String>,
+    pub blocklist_function : Vec<String>,
+    pub blocklist_type     : Vec<String>,
+    pub blocklist_var      : Vec<String>,
+    pub opaque_type        : Vec<String>,
+    pub enum_style         : Option<String>,
+    pub derive             : Option<Vec<String>>,
+    pub size_t_is_usize    : Option<bool>,
+    pub layout_tests       : Option<bool>,
+    pub doc_comments       : Option<bool>,
+}
+
+impl BindgenOptions {
+    /// Merges options of the libraries sharing one bindgen run. The files of
+    /// `allowlist-path` are joined. The other options apply to the whole run, so the
+    /// libraries should agree on them, including allowlists and blocklists of items
+    /// by name, which would apply to the headers of every library otherwise.
+    pub fn merge<'a>( libraries: impl IntoIterator<Item=(&'a String, &'a BindgenOptions)> ) -> Result<BindgenOptions> {
+        let mut merged = BindgenOptions::default();
+        let mut set_by = BTreeMap::new(); // option -> the first library setting it
+        let mut lists_of = None; // the first library, whose lists others should agree on
+
+        for (lib_name, options) in libraries {
+            match lists_of {
+                None => {
+                    lists_of = Some( lib_name );
+                    merged.allowlist_function = options.allowlist_function.clone();
+                    merged.allowlist_type     = options.allowlist_type    .clone();
+                    merged.allowlist_var      = options.allowlist_var     .clone();
+                    merged.blocklist_function = options.blocklist_function.clone();
+                    merged.blocklist_type     = options.blocklist_type    .clone();
+                    merged.blocklist_var      = options.blocklist_var     .clone();
+                    merged.opaque_type        = options.opaque_type       .clone();
+                },
+                Some( first ) if !merged.has_lists_of( options ) => return Err( anyhow!(
+                    "libraries {} and {} sharing one bindgen run have different allowlists or blocklists, \
+                    applying to the headers of both, which `modules = true` allows", first, lib_name )),
+                Some(_) => (),
+            }
+            merged.allowlist_path.extend_from_slice( &options.allowlist_path );
+
+            let derive = options.derive.clone().map( |mut derive| { derive.sort(); derive.dedup(); derive });
+            merge_option( &mut merged.enum_style     , &options.enum_style     , "enum-style"     , lib_name, &mut set_by )?;
+            merge_option( &mut merged.derive         , &derive                 , "derive"         , lib_name, &mut set_by )?;
+            merge_option( &mut merged.size_t_is_usize, &options.size_t_is_usize, "size_t-is-usize", lib_name, &mut set_by )?;
+            merge_option( &mut merged.layout_tests   , &options.layout_tests   , "layout-tests"   , lib_name, &mut set_by )?;
+            merge_option( &mut merged.doc_comments   , &options.doc_comments   , "doc-comments"   , lib_name, &mut set_by )?;
+        }
+        Ok( merged )
+    }
+
+    /// Whether `other` has the same allowlists and blocklists of items by name.
+    fn has_lists_of( &self, other: &BindgenOptions ) -> bool {
+        self.allowlist_function == other.allowlist_function
+            && self.allowlist_type     == other.allowlist_type
+            && self.allowlist_var      == other.allowlist_var
+            && self.blocklist_function == other.blocklist_function
+            && self.blocklist_type     == other.blocklist_type
+            && self.blocklist_var      == other.blocklist_var
+            && self.opaque_type        == other.opaque_type
+    }
+}
+
+/// Sets `merged` to `value` of the library, unless set differently by another one.
+fn merge_option<'a, T: Clone + PartialEq>( merged: &mut Option<T>, value: &Option<T>, key: &'static str, lib_name: &'a String,
+    set_by: &mut BTreeMap<&'static str,&'a String> ) -> Result<()>
+{
+    match (merged.as_ref(), value) {
+        (_, None) => Ok(()),
+        (None, Some( value )) => {
+            set_by.insert( key, lib_name );
+            *merged = Some( value.clone() );
+            Ok(())
+        },
+        (Some( merged ), Some( value )) if merged == value => Ok(()),
+        (Some(_), Some(_)) => Err( anyhow!( "libraries {} and {} sharing one bindgen run set `bindgen.{}` differently, \
+            which `modules = true` allows", set_by[ key ], lib_name, key )),
+    }
+}
+
+/// An entry of `dependencies` or `header-dependencies`.
+#[derive( Debug, PartialEq )]
+pub struct Dependency {
+    pub name    : String,
+    pub os      : Option<String>,
+    pub version : Option<VersionReq>,
+}
+
+impl Dependency {
+    /// Whether the dependency applies to the target OS.
+    pub fn is_enabled( &self ) -> bool {
+        self.os.as_deref().is_none_or( match_os )
+    }
+}
+
+/// Candidate file names of one library, the first existing one will be linked.
+pub type LibGroup = Vec<String>;
+
+fn match_os( name: &str ) -> bool {
+    match name {
+        "android"   => cfg!( target_os = "android"   ),
+        "dragonfly" => cfg!( target_os = "dragonfly" ),
+        "freebsd"   => cfg!( target_os = "freebsd"   ),
+        "ios"       => cfg!( target_os = "ios"       ),
+        "linux"     => cfg!( target_os = "linux"     ),
+        "macos"     => cfg!( target_os = "macos"     ),
+        "netbsd"    => cfg!( target_os = "netbsd"    ),
+        "openbsd"   => cfg!( target_os = "openbsd"   ),
+        "windows"   => cfg!( target_os = "windows"   ),
+        "unix"      => cfg!(              unix       ),
+        _           => false,
+    }
+}
+
+impl Metadata {
+    /// Decodes the metadata collected by inwelling from one downstream package.
+    pub fn parse( package: &inwelling::Package ) -> Result<Self> {
+        let origin = Rc::new( Origin {
+            package  : package.name.clone(),
+            manifest : package.manifest.clone(),
+        });
+        let at = At{ origin: &origin, key: ROOT_KEY.to_owned() };
+        let fields = Fields::new( &package.metadata, at, METADATA_KEYS )?;
+
+        let entries = fields.get::<Vec<BuildEntry>>( "build" )?.unwrap_or_default();
+        let modules = fields.get::<bool>( "modules" )?.unwrap_or_default();
+
+        let mut specs = BTreeMap::new();
+        if let Some( value ) = fields.value( "spec" ) {
+            let at = fields.at.child( "spec" );
+            let table = value.as_table().ok_or_else( || at.mismatch( "table", value ))?;
+            for (name, value) in table {
+                specs.insert( name.clone(), Spec::parse( value, at.child( name ), &origin )? );
+            }
+        }
+
+        // Headers given in `build` make up the spec of a library without one.
+        let mut build = Vec::with_capacity( entries.len() );
+        for (index, entry) in entries.into_iter().enumerate() {
+            if let Some( headers ) = entry.headers {
+                let at = fields.at.child( "build" ).index( index );
+                if specs.contains_key( &entry.name ) {
+                    return Err( at.child( "headers" ).error( format!(
+                        "library `{}` has a spec, where its `headers` should be specified", entry.name )));
+                }
+                let table = toml::Table::from_iter([ ( "headers".to_owned(), Toml::Array( headers.into_iter().map( Toml::String ).collect() ))]);
+                specs.insert( entry.name.clone(), Spec::parse( &Toml::Table( table ), at, &origin )? );
+            }
+            build.push( Build{ name: entry.name, optional: entry.optional });
+        }
+
+        Ok( Metadata{ origin, build, modules, specs })
+    }
+}
+
+impl Spec {
+    fn parse( value: &Toml, at: At, origin: &Origin ) -> Result<Self> {
+        let fields = Fields::new( value, at, SPEC_KEYS )?;
+
+        let for_docs_rs = fields.get::<String>( "for-docs-rs" )?.map( |path| ManifestPath::new( path, origin ));
+
+        let bundled_include_dir = fields.get::<String>( "bundled-include-dir" )?.map( |path| ManifestPath::new( path, origin ));
+
+        let vendored = fields.get::<String>( "vendored" )?.map( |path| ManifestPath::new( path, origin ));
+
+        let probe = fields.get::<Vec<String>>( "probe" )?;
+        for (index, strategy) in probe.iter().flatten().enumerate() {
+            if !PROBE_STRATEGIES.contains( &strategy.as_str() ) {
+                return Err( fields.at.child( "probe" ).index( index ).error( unknown( "probe strategy", strategy, PROBE_STRATEGIES )));
+            }
+        }
+
+        let version_cfgs = fields.get::<Vec<String>>( "version-cfgs" )?.unwrap_or_default();
+        for (index, version) in version_cfgs.iter().enumerate() {
+            if version.split( '.' ).any( |segment| segment.is_empty() || !segment.bytes().all( |b| b.is_ascii_digit() )) {
+                return Err( fields.at.child( "version-cfgs" ).index( index ).error( format!(
+                    "expected a version of numbers separated by dots, e.g. \"8.6\", found {:?}", version )));
+            }
+        }
+
+        Ok( Spec {
+            required            : fields.get( "required"            )?.unwrap_or_default(),
+            pc_alias            : fields.get( "pc-alias"            )?.unwrap_or_default(),
+            headers             : fields.get( "headers"             )?.unwrap_or_default(),
+            dependencies        : fields.get::<Dependencies>( "dependencies"        )?.unwrap_or_default().0,
+            header_dependencies : fields.get::<Dependencies>( "header-dependencies" )?.unwrap_or_default().0,
+            config_script       : fields.get( "config-script"       )?.unwrap_or_default(),
+            config_file         : fields.get( "config-file"         )?,
+            cmake               : fields.get( "cmake"               )?,
+            vendored            ,
+            probe               ,
+            exe                 : fields.get( "exe"                 )?.unwrap_or_default(),
+            bindir              : fields.get( "bindir"              )?.unwrap_or_else( || search::default_dirs( search::BINDIR )),
+            libdir              : fields.get( "libdir"              )?.unwrap_or_else( || search::default_dirs( search::LIBDIR )),
+            include_root        : fields.get( "include-root"        )?.unwrap_or_else( || search::default_dirs( search::INCLUDE_ROOT )),
+            includedir          : fields.get( "includedir"          )?.unwrap_or_default(),
+            libs                : fields.get::<Libs>( "libs"         )?.unwrap_or_default().0,
+            libs_private        : fields.get::<Libs>( "libs-private" )?.unwrap_or_default().0,
+            for_docs_rs         ,
+            bundled_include_dir ,
+            bindgen             : fields
+                .value( "bindgen" )
+                .map( |value| BindgenOptions::parse( value, fields.at.child( "bindgen" )))
+                .transpose()?
+                .unwrap_or_default(),
+            version             : fields.get( "version"             )?,
+            version_macro       : fields.get( "version-macro"       )?,
+            version_args        : fields.get( "version-args"        )?.unwrap_or_default(),
+            version_cfgs        ,
+        })
+    }
+}
+
+impl BindgenOptions {
+    fn parse( value: &Toml, at: At ) -> Result<Self> {
+        let fields = Fields::new( value, at, BINDGEN_KEYS )?;
+
+        let enum_style = fields.get::<String>( "enum-style" )?;
+        if let Some( enum_style ) = &enum_style {
+            if !ENUM_STYLES.contains( &enum_style.as_str() ) {
+                return Err( fields.at.child( "enum-style" ).error( unknown( "enum style", enum_style, ENUM_STYLES )));
+            }
+        }
+
+        let blocklist_var = fields.get::<Vec<String>>( "blocklist-var" )?.unwrap_or_default();
+        for (index, regex) in blocklist_var.iter().enumerate() {
+            if let Err( err ) = Regex::new( regex ) {
+                return Err( fields.at.child( "blocklist-var" ).index( index ).error( format!( "invalid regex: {}", err )));
+            }
+        }
+
+        let derive = fields.get::<Vec<String>>( "derive" )?;
+        for (index, name) in derive.iter().flatten().enumerate() {
+            if !DERIVES.contains( &name.as_str() ) {
+                return Err( fields.at.child( "derive" ).index( index ).error( unknown( "derive", name, DERIVES )));
+            }
+        }
+
+        Ok( BindgenOptions {
+            allowlist_function : fields.get( "allowlist-function" )?.unwrap_or_default(),
+            allowlist_type     : fields.get( "allowlist-type"     )?.unwrap_or_default(),
+            allowlist_var      : fields.get( "allowlist-var"      )?.unwrap_or_default(),
+            allowlist_path     : fields.get( "allowlist-path"     )?.unwrap_or_default(),
+            blocklist_function : fields.get( "blocklist-function" )?.unwrap_or_default(),
+            blocklist_type     : fields.get( "blocklist-type"     )?.unwrap_or_default(),
+            blocklist_var      ,
+            opaque_type        : fields.get( "opaque-type"        )?.unwrap_or_default(),
+            enum_style         ,
+            derive             ,
+            size_t_is_usize    : fields.get( "size_t-is-usize"    )?,
+            layout_tests       : fields.get( "layout-tests"       )?,
+            doc_comments       : fields.get( "doc-comments"       )?,
+        })
+    }
+}
+
+/// Decoding position: the contributing package and the key path.
+#[derive( Clone )]
+struct At<'a> {
+    origin : &'a Origin,
+    key    : String,
+}
+
+impl<'a> At<'a> {
+    fn child( &self, key: &str ) -> At<'a> {
+        At{ origin: self.origin, key: format!( "{}.{}", self.key, key )}
+    }
+
+    fn index( &self, index: usize ) -> At<'a> {
+        At{ origin: self.origin, key: format!( "{}[{}]", self.key, index )}
+    }
+
+    fn error( &self, message: String ) -> anyhow::Error {
+        anyhow::Error::new( SpecError {
+            package  : self.origin.package.clone(),
+            manifest : self.origin.manifest.clone(),
+            key      : self.key.clone(),
+            message  ,
+        })
+    }
+
+    fn mismatch( &self, expected: &str, found: &Toml ) -> anyhow::Error {
+        self.error( format!( "expected {}, found {}", expected, found.type_str() ))
+    }
+}
+
+/// A table whose keys have been checked against the known ones.
+struct Fields<'a> {
+    at    : At<'a>,
+    table : &'a toml::Table,
+}
+
+impl<'a> Fields<'a> {
+    fn new( value: &'a Toml, at: At<'a>, known: &[&str] ) -> Result<Self> {
+        let table = value.as_table().ok_or_else( || at.mismatch( "table", value ))?;
+        for key in table.keys() {
+            if !known.contains( &key.as_str() ) {
+                return Err( at.child( key ).error( unknown( "key", key, known )));
+            }
+        }
+        Ok( Fields{ at, table })
+    }
+
+    fn value( &self, key: &str ) -> Option<&'a Toml> {
+        self.table.get( key )
+    }
+
+    fn get<T: FromToml>( &self, key: &str ) -> Result<Option<T>> {
+        self.table
+            .get( key )
+            .map( |value| T::from_toml( value, &self.at.child( key )))
+            .transpose()
+    }
+}
+
+/// Builds the message for an unrecognized name, suggesting the closest known one.
+pub fn unknown( what: &str, name: &str, known: &[&str] ) -> String {
+    match did_you_mean( name, known ) {
+        Some( suggestion ) => format!( "unknown {} `{}`, did you mean `{}`?", what, name, suggestion ),
+        None => format!( "unknown {} `{}`, expected one of {}", what, name,
+            known.iter().map( |k| format!( "`{}`", k )).collect::<Vec<_>>().join( ", " )),
+    }
+}
+
+fn did_you_mean<'k>( name: &str, known: &[&'k str] ) -> Option<&'k str> {
+    let threshold = std::cmp::max( 1, name.chars().count() / 3 );
+    known
+        .iter()
+        .map( |candidate| (edit_distance( name, candidate ), *candidate) )
+        .filter( |(distance, _)| *distance <= threshold )
+        .min_by_key( |(distance, _)| *distance )
+        .map( |(_, candidate)| candidate )
+}
+
+fn edit_distance( a: &str, b: &str ) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[ j+1 ];
+            row[ j+1 ] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min( above ).min( row[j] )
+            };
+            diagonal = above;
+        }
+    }
+    row[ b.len() ]
+}
+
+trait FromToml: Sized {
+    fn from_toml( value: &Toml, at: &At ) -> Result<Self>;
+}
+
+impl FromToml for String {
+    fn from_toml( value: &Toml, at: &At ) -> Result<Self> {
+        value.as_str().map( str::to_owned ).ok_or_else( || at.mismatch( "string", value ))
+    }
+}
+
+impl FromToml for VersionReq {
+    fn from_toml( value: &Toml, at: &At ) -> Result<Self> {
+        VersionReq::parse( &String::from_toml( value, at )? ).map_err( |err| at.error( err.to_string() ))
+    }
+}
+
+impl FromToml for bool {
+    fn from_toml( value: &Toml, at: &At ) -> Result<Self> {
+        value.as_bool().ok_or_else( || at.mismatch( "boolean", value ))
+    }
+}
+
+impl<T: FromToml> FromToml for Vec<T> {
+    fn from_toml( value: &Toml, at: &At ) -> Result<Self> {
+        value
+            .as_array()
+            .ok_or_else( || at.mismatch( "array", value ))?
+            .iter()
+            .enumerate()
+            .map( |(index, value)| T::from_toml( value, &at.index( index )))
+            .collect()
+    }
+}
+
+impl FromToml for ConfigFile {
+    fn from_toml( value: &Toml, at: &At ) -> Result<Self> {
+        let fields = Fields::new( value, at.clone(), CONFIG_FILE_KEYS )?;
+        Ok( ConfigFile {
+            name         : fields.get( "name"         )?.ok_or_else( || at.error( "missing key `name`".to_owned() ))?,
+            cflags       : fields.get( "cflags"       )?.unwrap_or_default(),
+            libs         : fields.get( "libs"         )?.unwrap_or_default(),
+            libs_private : fields.get( "libs-private" )?.unwrap_or_default(),
+            version      : fields.get( "version"      )?.unwrap_or_default(),
+        })
+    }
+}
+
+impl FromToml for CMakePackage {
+    fn from_toml( value: &Toml, at: &At ) -> Result<Self> {
+        let fields = Fields::new( value, at.clone(), CMAKE_KEYS )?;
+        Ok( CMakePackage {
+            package    : fields.get( "package"    )?.ok_or_else( || at.error( "missing key `package`".to_owned() ))?,
+            components : fields.get( "components" )?.unwrap_or_default(),
+            targets    : fields.get( "targets"    )?.unwrap_or_default(),
+        })
+    }
+}
+
+/// `build = ["zlib"]`, or `build = [{ name = "zlib", headers = ["zlib.h"] }]`.
+struct BuildEntry {
+    name     : String,
+    headers  : Option<Vec<String>>,
+    optional : bool,
+}
+
+impl FromToml for BuildEntry {
+    fn from_toml( value: &Toml, at: &At ) -> Result<Self> {
+        match value {
+            Toml::String( name ) => Ok( BuildEntry{ name: name.clone(), headers: None, optional: false }),
+            Toml::Table(_) => {
+                let fields = Fields::new( value, at.clone(), BUILD_KEYS )?;
+                let name = fields.get::<String>( "name" )?.ok_or_else( || at.error( "missing key `name`".to_owned() ))?;
+                let headers = fields.get( "headers" )?;
+                let optional = fields.get( "optional" )?.unwrap_or_default();
+                Ok( BuildEntry{ name, headers, optional })
+            },
+            _ => Err( at.mismatch( "string or table", value )),
+        }
+    }
+}
+
+/// `dependencies = ["a", "b"]`, or `[dependencies] a = { os = "linux" }`.
+#[derive( Default )]
+struct Dependencies( Vec<Dependency> );
+
+impl FromToml for Dependencies {
+    fn from_toml( value: &Toml, at: &At ) -> Result<Self> {
+        match value {
+            Toml::Array(_) => Ok( Dependencies( Vec::<String>::from_toml( value, at )?
+                .into_iter()
+                .map( |name| Dependency{ name, os: None, version: None })
+                .collect() )),
+            Toml::Table( table ) => table
+                .iter()
+                .map( |(name, value)| {
+                    let at = at.child( name );
+                    let fields = Fields::new( value, at, DEPENDENCY_KEYS )?;
+                    let os = fields.get::<String>( "os" )?;
+                    if let Some( os ) = &os {
+                        if !OS_NAMES.contains( &os.as_str() ) {
+                            return Err( fields.at.child( "os" ).error( unknown( "os", os, OS_NAMES )));
+                        }
+                    }
+                    let version = fields.get( "version" )?;
+                    Ok( Dependency{ name: name.clone(), os, version })
+                })
+                .collect::<Result<_>>()
+                .map( Dependencies ),
+            _ => Err( at.mismatch( "array or table", value )),
+        }
+    }
+}
+
+/// `libs = ["libfoo.so", "libbar.so"]` requiring every file, or
+/// `[libs] foo = ["libfoo.so", "libfoo.a"]` requiring one file per entry.
+#[derive( Default )]
+struct Libs( Vec<LibGroup> );
+
+impl FromToml for Libs {
+    fn from_toml( value: &Toml, at: &At ) -> Result<Self> {
+        match value {
+            Toml::Array(_) => Ok( Libs( Vec::<String>::from_toml( value, at )?
+                .into_iter()
+                .map( |lib_name| vec![ lib_name ])
+                .collect() )),
+            Toml::Table( table ) => table
+                .iter()
+                .map( |(name, value)| Vec::<String>::from_toml( value, &at.child( name )))
+                .collect::<Result<_>>()
+                .map( Libs ),
+            _ => Err( at.mismatch( "array or table", value )),
+        }
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    fn bindgen_options( text: &str ) -> Result<BindgenOptions> {
+        let origin = Origin{ package: "downstream".to_owned(), manifest: PathBuf::from( "/downstream/Cargo.toml" )};
+        let value = toml::from_str::<Toml>( text ).unwrap();
+        BindgenOptions::parse( &value, At{ origin: &origin, key: "bindgen".to_owned() })
+    }
+
+    fn metadata( package: &str, text: &str ) -> Result<Metadata> {
+        Metadata::parse( &inwelling::Package {
+            name     : package.to_owned(),
+            manifest : PathBuf::from( format!( "/{}/Cargo.toml", package )),
+            metadata : toml::from_str( text ).unwrap(),
+            rs_paths : None,
+        })
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let err = metadata( "tk", "[spec.tk86]\nheader = [\"tk.h\"]" ).unwrap_err();
+        assert_eq!( err.to_string(), "invalid clib metadata in package `tk` (/tk/Cargo.toml), \
+            at `package.metadata.inwelling.clib.spec.tk86.header`: unknown key `header`, did you mean `headers`?" );
+
+        let err = metadata( "tk", "[spec.tk86.bindgen]\nalowlist-function = [\"Tk_.*\"]" ).unwrap_err();
+        assert!( err.to_string().ends_with( "unknown key `alowlist-function`, did you mean `allowlist-function`?" ), "{}", err );
+
+        let err = metadata( "tk", "bild = [\"tk86\"]" ).unwrap_err();
+        assert!( err.to_string().ends_with( "unknown key `bild`, did you mean `build`?" ), "{}", err );
+
+        let err = metadata( "tk", "[spec.tk86.cmake]\npackage = \"TK\"\nfoo = 1" ).unwrap_err();
+        assert!( err.to_string().ends_with( "unknown key `foo`, expected one of `package`, `components`, `targets`" ), "{}", err );
+    }
+
+    #[test]
+    fn suggests_similar_names() {
+        assert_eq!( edit_distance( "header", "headers" ), 1 );
+        assert_eq!( edit_distance( "kitten", "sitting" ), 3 );
+        assert_eq!( edit_distance( "", "abc" ), 3 );
+        assert_eq!( did_you_mean( "pkgconfig", PROBE_STRATEGIES ), Some( "pkg-config" ));
+        assert_eq!( did_you_mean( "libz", &[ "libs", "libdir" ]), Some( "libs" ));
+        assert_eq!( did_you_mean( "xyz", &[ "libs", "libdir" ]), None );
+    }
+
+    #[test]
+    fn compares_paths_as_written_in_specs() {
+        let text = r#"
+            [spec.tk86]
+            headers = ["tk.h"]
+            vendored = "vendor/tk"
+            for-docs-rs = "docs/tk86.rs"
+        "#;
+        let tk = metadata( "tk", text ).unwrap().specs.remove( "tk86" ).unwrap();
+        let tkimg = metadata( "tkimg", text ).unwrap().specs.remove( "tk86" ).unwrap();
+        assert_eq!( tk.vendored.as_deref(), Some( Path::new( "/tk/vendor/tk" )));
+        assert_eq!( tkimg.vendored.as_deref(), Some( Path::new( "/tkimg/vendor/tk" )));
+        assert_eq!( tk, tkimg );
+
+        let other = metadata( "tkimg", &text.replace( "vendor/tk", "vendor/tk86" )).unwrap().specs.remove( "tk86" ).unwrap();
+        assert_ne!( tk, other );
+    }
+
+    #[test]
+    fn merges_bindgen_options() {
+        let (tcl, tk, x11) = ("tcl".to_owned(), "tk".to_owned(), "x11".to_owned());
+        let tcl_options = bindgen_options( r#"
+            blocklist-type = ["FILE"]
+            allowlist-path = ["tclDecls.h"]
+            derive = ["Debug", "Copy"]
+            size_t-is-usize = true
+        "# ).unwrap();
+        let tk_options = bindgen_options( r#"
+            blocklist-type = ["FILE"]
+            allowlist-path = ["tkDecls.h"]
+            derive = ["Copy", "Debug"]
+        "# ).unwrap();
+        let x11_options = bindgen_options( r#"
+            blocklist-type = ["FILE"]
+            size_t-is-usize = false
+        "# ).unwrap();
+
+        let merged = BindgenOptions::merge([ (&tcl, &tcl_options), (&tk, &tk_options) ]).unwrap();
+        assert_eq!( merged.blocklist_type, [ "FILE" ]);
+        assert_eq!( merged.allowlist_path, [ "tclDecls.h", "tkDecls.h" ]);
+        assert_eq!( merged.derive.as_deref(), Some( [ "Copy".to_owned(), "Debug".to_owned() ].as_slice() ));
+        assert_eq!( merged.size_t_is_usize, Some( true ));
+
+        let err = BindgenOptions::merge([ (&tcl, &tcl_options), (&tk, &tk_options), (&x11, &x11_options) ]).unwrap_err();
+        assert!( err.to_string().starts_with( "libraries tcl and x11 sharing one bindgen run set `bindgen.size_t-is-usize` differently" ), "{}", err );
+    }
+
+    #[test]
+    fn rejects_different_lists_in_one_bindgen_run() {
+        let (tcl, tk) = ("tcl".to_owned(), "tk".to_owned());
+        let tcl_options = BindgenOptions::default();
+        let tk_options = bindgen_options( r#"allowlist-function = ["Tk_.*"]"# ).unwrap();
+
+        assert!( BindgenOptions::merge([ (&tk, &tk_options) ]).is_ok() );
+        let err = BindgenOptions::merge([ (&tcl, &tcl_options), (&tk, &tk_options) ]).unwrap_err();
+        assert!( err.to_string().starts_with( "libraries tcl and tk sharing one bindgen run have different allowlists or blocklists" ), "{}", err );
+    }
+
+    #[test]
+    fn checks_blocklist_var() {
+        assert!( bindgen_options( r#"blocklist-var = ["TCL_.*"]"# ).is_ok() );
+        let err = bindgen_options( r#"blocklist-var = ["TCL_.*", "TK_("]"# ).unwrap_err();
+        assert!( err.to_string().contains( "at `bindgen.blocklist-var[1]`: invalid regex" ), "{}", err );
+    }
+}
@@ -0,0 +1,23 @@
+//! Unit tests of the build script of clib. Its modules are compiled here as a
+//! library, for `cargo test` not to build the build script as a test target.
+
+#![cfg( test )]
+#![allow( dead_code )]
+
+#[path = "../bindings.rs"    ] mod bindings;
+#[path = "../cmake.rs"       ] mod cmake;
+#[path = "../compiler.rs"    ] mod compiler;
+#[path = "../configscript.rs"] mod configscript;
+#[path = "../libs.rs"        ] mod libs;
+#[path = "../modules.rs"     ] mod modules;
+#[path = "../overrides.rs"   ] mod overrides;
+#[path = "../pcfile.rs"      ] mod pcfile;
+#[path = "../pkgconf.rs"     ] mod pkgconf;
+#[path = "../probe.rs"       ] mod probe;
+#[path = "../report.rs"      ] mod report;
+#[path = "../search.rs"      ] mod search;
+#[path = "../spec.rs"        ] mod spec;
+#[path = "../version.rs"     ] mod version;
+
+// The same as in build/main.rs.
+const UTF8_PATH: &str = "path should be valid UTF-8 string.";
@@ -0,0 +1,297 @@
+//! Version requirements of libraries, e.g. `version = ">=8.6.10, <9"`.
+
+use anyhow::{Result, anyhow};
+
+use std::{
+    cell::Cell,
+    cmp::Ordering,
+    fmt::{self, Display},
+    ops::Bound,
+};
+
+thread_local! {
+    static REJECTED: Cell<bool> = const { Cell::new( false ) };
+}
+
+/// Whether any library has been found of a version failing a requirement since the
+/// last call, telling a library of another version apart from one not found.
+pub fn take_rejected() -> bool {
+    REJECTED.with( Cell::take )
+}
+
+/// Records a library found of a version failing a requirement, or of a version
+/// unknown, for `take_rejected()`.
+pub fn reject() {
+    REJECTED.with( |rejected| rejected.set( true ));
+}
+
+/// Comma separated comparisons, each of which is one of `>=`, `>`, `<=`, `<`,
+/// `=` followed by a version. A version without comparison operator means `>=`.
+#[derive( Clone, Debug, PartialEq )]
+pub struct VersionReq {
+    text : String,
+    min  : Bound<String>,
+    max  : Bound<String>,
+}
+
+impl Display for VersionReq {
+    fn fmt( &self, f: &mut fmt::Formatter ) -> fmt::Result {
+        write!( f, "{}", self.text )
+    }
+}
+
+impl VersionReq {
+    pub fn parse( text: &str ) -> Result<Self> {
+        let mut req = VersionReq{ text: text.trim().to_owned(), min: Bound::Unbounded, max: Bound::Unbounded };
+
+        for comparison in text.split( ',' ).map( str::trim ) {
+            let (op, version) = ["=>", ">=", "<=", "==", ">", "<", "="]
+                .iter()
+                .find_map( |op| comparison.strip_prefix( op ).map( |version| (*op, version.trim()) ))
+                .unwrap_or(( ">=", comparison ));
+
+            if version.is_empty() || !version.chars().all( |c| c.is_ascii_alphanumeric() || ".-_+~".contains( c )) {
+                return Err( anyhow!( "invalid version `{}` in requirement `{}`", version, text ));
+            }
+
+            let version = version.to_owned();
+            match op {
+                ">=" | "=>" => req.raise_min( Bound::Included( version )),
+                ">"         => req.raise_min( Bound::Excluded( version )),
+                "<="        => req.lower_max( Bound::Included( version )),
+                "<"         => req.lower_max( Bound::Excluded( version )),
+                _           => {
+                    req.raise_min( Bound::Included( version.clone() ));
+                    req.lower_max( Bound::Included( version ));
+                },
+            }
+        }
+        Ok( req )
+    }
+
+    /// Both requirements.
+    pub fn and( &self, other: &VersionReq ) -> VersionReq {
+        let mut req = self.clone();
+        req.text = format!( "{}, {}", self.text, other.text );
+        req.raise_min( other.min.clone() );
+        req.lower_max( other.max.clone() );
+        req
+    }
+
+    /// The range for `pkg_config::Config::range_version()`.
+    pub fn range( &self ) -> (Bound<&str>, Bound<&str>) {
+        (self.min.as_ref().map( String::as_str ), self.max.as_ref().map( String::as_str ))
+    }
+
+    /// Whether every version matching the requirement is not older than `version`.
+    pub fn ensures_at_least( &self, version: &str ) -> bool {
+        match &self.min {
+            Bound::Included( min ) | Bound::Excluded( min ) => compare( min, version ) != Ordering::Less,
+            Bound::Unbounded                                => false,
+        }
+    }
+
+    pub fn matches( &self, version: &str ) -> bool {
+        let above_min = match &self.min {
+            Bound::Included( min ) => compare( version, min ) != Ordering::Less,
+            Bound::Excluded( min ) => compare( version, min ) == Ordering::Greater,
+            Bound::Unbounded       => true,
+        };
+        let below_max = match &self.max {
+            Bound::Included( max ) => compare( version, max ) != Ordering::Greater,
+            Bound::Excluded( max ) => compare( version, max ) == Ordering::Less,
+            Bound::Unbounded       => true,
+        };
+        above_min && below_max
+    }
+
+    /// Checks the `version` found for a library, e.g. in its .pc file.
+    pub fn check( &self, pkg_name: &str, version: &str, found_in: &str ) -> Result<()> {
+        if self.matches( version ) {
+            Ok(())
+        } else {
+            reject();
+            Err( anyhow!( "{} of version {} found in {}, but version `{}` is required", pkg_name, version, found_in, self ))
+        }
+    }
+
+    fn raise_min( &mut self, min: Bound<String> ) {
+        let higher = match (&self.min, &min) {
+            (Bound::Unbounded, _) => true,
+            (_, Bound::Unbounded) => false,
+            (Bound::Included( old ) | Bound::Excluded( old ), Bound::Included( new )) => compare( new, old ) == Ordering::Greater,
+            (Bound::Included( old ) | Bound::Excluded( old ), Bound::Excluded( new )) => compare( new, old ) != Ordering::Less,
+        };
+        if higher {
+            self.min = min;
+        }
+    }
+
+    fn lower_max( &mut self, max: Bound<String> ) {
+        let lower = match (&self.max, &max) {
+            (Bound::Unbounded, _) => true,
+            (_, Bound::Unbounded) => false,
+            (Bound::Included( old ) | Bound::Excluded( old ), Bound::Included( new )) => compare( new, old ) == Ordering::Less,
+            (Bound::Included( old ) | Bound::Excluded( old ), Bound::Excluded( new )) => compare( new, old ) != Ordering::Greater,
+        };
+        if lower {
+            self.max = max;
+        }
+    }
+}
+
+/// Compares versions as pkg-config does, segment by segment: runs of digits compare
+/// numerically and are newer than runs of letters, and other characters separate
+/// segments, except that `~` marks a pre-release, e.g. "1.0~rc1" is older than "1.0".
+/// Otherwise a version with more segments is newer, e.g. "1.0a" than "1.0".
+pub fn compare( a: &str, b: &str ) -> Ordering {
+    let is_separator = |c: char| !c.is_ascii_alphanumeric() && c != '~';
+    let (mut a, mut b) = (a, b);
+
+    loop {
+        a = a.trim_start_matches( is_separator );
+        b = b.trim_start_matches( is_separator );
+
+        match (a.strip_prefix( '~' ), b.strip_prefix( '~' )) {
+            (Some( rest_a ), Some( rest_b )) => {
+                (a, b) = (rest_a, rest_b);
+                continue;
+            },
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => (),
+        }
+        if a.is_empty() || b.is_empty() {
+            break;
+        }
+
+        let numeric = a.starts_with( |c: char| c.is_ascii_digit() );
+        let segment_len = |version: &str| version
+            .find( |c: char| if numeric { !c.is_ascii_digit() } else { !c.is_ascii_alphabetic() })
+            .unwrap_or( version.len() );
+        let (segment_a, rest_a) = a.split_at( segment_len( a ));
+        let (segment_b, rest_b) = b.split_at( segment_len( b ));
+        if segment_b.is_empty() {
+            // Segments of different kinds: digits are newer.
+            return if numeric { Ordering::Greater } else { Ordering::Less };
+        }
+
+        let ordering = if numeric {
+            let (x, y) = (segment_a.trim_start_matches( '0' ), segment_b.trim_start_matches( '0' ));
+            x.len().cmp( &y.len() ).then_with( || x.cmp( y ))
+        } else {
+            segment_a.cmp( segment_b )
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        (a, b) = (rest_a, rest_b);
+    }
+
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
+
+/// Extracts the first version-like word, e.g. "8.6.12" from "tclsh 8.6.12".
+pub fn find_version( text: &str ) -> Option<String> {
+    text
+        .split( |c: char| c.is_whitespace() || c == '"' || c == '\'' || c == ',' || c == '(' || c == ')' )
+        .map( |word| word.trim_start_matches( ['v', 'V'] ).trim_end_matches( '.' ))
+        .find( |word| word.starts_with( |c: char| c.is_ascii_digit() )
+            && word.contains( '.' )
+            && word.chars().all( |c| c.is_ascii_alphanumeric() || ".-_+~".contains( c )))
+        .map( str::to_owned )
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_numerically() {
+        assert_eq!( compare( "8.6.13", "8.6.13" ), Ordering::Equal );
+        assert_eq!( compare( "8.10", "8.9" ), Ordering::Greater );
+        assert_eq!( compare( "8.6", "8.6.0" ), Ordering::Less );
+        assert_eq!( compare( "1.010", "1.9" ), Ordering::Greater );
+        assert_eq!( compare( "1.01", "1.1" ), Ordering::Equal );
+        assert_eq!( compare( "2.0", "10.0" ), Ordering::Less );
+    }
+
+    #[test]
+    fn compares_pre_release_and_suffixed_versions() {
+        assert_eq!( compare( "1.0~rc1", "1.0" ), Ordering::Less );
+        assert_eq!( compare( "1.0~rc1", "1.0~rc2" ), Ordering::Less );
+        assert_eq!( compare( "1.0~rc1", "0.9" ), Ordering::Greater );
+        assert_eq!( compare( "1.0a", "1.0" ), Ordering::Greater );
+        assert_eq!( compare( "1.0a", "1.0b" ), Ordering::Less );
+        assert_eq!( compare( "1.0a", "1.0.1" ), Ordering::Less );
+        assert_eq!( compare( "2.9.14-r1", "2.9.14" ), Ordering::Greater );
+        assert_eq!( compare( "8.6b1", "8.6.0" ), Ordering::Less );
+    }
+
+    #[test]
+    fn matches_bounds() {
+        let req = VersionReq::parse( ">=8.6.10, <9" ).unwrap();
+        assert!( req.matches( "8.6.10" ));
+        assert!( req.matches( "8.6.13" ));
+        assert!( !req.matches( "8.6.9" ));
+        assert!( !req.matches( "9" ));
+        assert!( !req.matches( "9.0" ));
+        assert!( req.matches( "9~rc1" ));
+
+        let req = VersionReq::parse( ">1.2, <=1.4" ).unwrap();
+        assert!( !req.matches( "1.2" ));
+        assert!( req.matches( "1.2.1" ));
+        assert!( req.matches( "1.4" ));
+        assert!( !req.matches( "1.4.1" ));
+
+        let req = VersionReq::parse( "=2.0" ).unwrap();
+        assert!( req.matches( "2.0" ));
+        assert!( !req.matches( "2.0.1" ));
+
+        // A version without operator means `>=`.
+        let req = VersionReq::parse( "1.2" ).unwrap();
+        assert!( req.matches( "1.2" ));
+        assert!( req.matches( "3" ));
+        assert!( !req.matches( "1.1" ));
+    }
+
+    #[test]
+    fn joins_requirements() {
+        let req = VersionReq::parse( ">=8.5" ).unwrap().and( &VersionReq::parse( ">=8.6, <9" ).unwrap() );
+        assert_eq!( req.to_string(), ">=8.5, >=8.6, <9" );
+        assert_eq!( req.range(), (Bound::Included( "8.6" ), Bound::Excluded( "9" )));
+        assert!( !req.matches( "8.5.19" ));
+        assert!( req.matches( "8.6.0" ));
+    }
+
+    #[test]
+    fn ensures_minimum_versions() {
+        let req = VersionReq::parse( ">=8.6.10, <9" ).unwrap();
+        assert!( req.ensures_at_least( "8.6" ));
+        assert!( req.ensures_at_least( "8.6.10" ));
+        assert!( !req.ensures_at_least( "8.7" ));
+        assert!( VersionReq::parse( ">8.6" ).unwrap().ensures_at_least( "8.6" ));
+        assert!( !VersionReq::parse( "<9" ).unwrap().ensures_at_least( "8.6" ));
+    }
+
+    #[test]
+    fn rejects_malformed_requirements() {
+        for text in [ "", ">=", "8.6,", ">= 8.6 beta", "8.6!", "<=>1" ] {
+            assert!( VersionReq::parse( text ).is_err(), "{:?} should be rejected", text );
+        }
+    }
+
+    #[test]
+    fn finds_versions() {
+        assert_eq!( find_version( "tclsh 8.6.12" ).as_deref(), Some( "8.6.12" ));
+        assert_eq!( find_version( "curl 8.4.0 (x86_64-pc-linux-gnu)" ).as_deref(), Some( "8.4.0" ));
+        assert_eq!( find_version( "Version v2.9.14." ).as_deref(), Some( "2.9.14" ));
+        assert_eq!( find_version( "#define TCL_PATCH_LEVEL \"8.6.13\"" ).as_deref(), Some( "8.6.13" ));
+        assert_eq!( find_version( "gcc 13" ), None );
+        assert_eq!( find_version( "no version here" ), None );
+    }
+}
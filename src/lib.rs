@@ -10,7 +10,7 @@
 //! 1. C libraries can be compiled with `bindgen`'s default configuration.
 //! 
 //! 2. C libraries provides pkg-config file, or its installation is consistent with
-//! the assumption of this crate.
+//!    the assumption of this crate.
 //! 
 //! # Usage demonstration: step-by-step explanation of tk library metadata
 //! 
@@ -55,6 +55,16 @@
 //! 
 //! This value tells `pkg-config` to find `tk.pc` if `tk86.pc` does not exist.
 //! 
+//! Once the .pc file is found, its Cflags affecting how headers are parsed, e.g.
+//! defines such as `-DUSE_FOO`, extra include paths and `-pthread`, are passed to
+//! bindgen along with the library's headers.
+//! 
+//! If the `pkg-config` executable is not available, crate clib reads .pc files by
+//! itself, from the directories in `PKG_CONFIG_PATH`, then the ones in
+//! `PKG_CONFIG_LIBDIR` or the default ones such as "/usr/lib/pkgconfig". Variables,
+//! `Requires` and `PKG_CONFIG_SYSROOT_DIR` are honored, as well as `Libs.private`
+//! and `Requires.private` when linking statically.
+//! 
 //! ## Enumerating header files of tk library
 //! 
 //! ```toml
@@ -78,6 +88,74 @@
 //! `[package.metadata.inwelling.clib.spec.tcl86]` of `examples/tcl/Cargo.toml`
 //! which will be collected by crate inwelling as well.
 //! 
+//! Dependencies need not be listed if the .pc file of the library says so. Each
+//! library in its `Requires` field having a spec, by its name or `pc-alias`, becomes
+//! one of the `dependencies`, and each one in `Requires.private` becomes one of the
+//! `header-dependencies`, along with their version requirements. They are appended
+//! to the ones listed in the spec.
+//! 
+//! Each library is probed only once, no matter how many libraries depend on it, and
+//! its link directives are emitted before those of its dependencies, as static
+//! linking requires. A library linked by several libraries is linked once, after
+//! all of them. A library depending on itself, directly or through other libraries,
+//! is reported as a dependency cycle, e.g. "dependency cycle detected: tk86 -> tcl86 -> tk86".
+//! Libraries are always processed in the order of their dependencies, and by name
+//! otherwise, so that the generated bindings and link directives are reproducible.
+//! 
+//! ## Config scripts
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib.spec.xml2]
+//! config-script = ["xml2-config"]
+//! 
+//! [package.metadata.inwelling.clib.spec.tcl86.config-file]
+//! name = ["tclConfig.sh"]
+//! cflags = ["TCL_INCLUDE_SPEC"]
+//! libs = ["TCL_LIB_SPEC"]
+//! libs-private = ["TCL_LIBS"]
+//! version = ["TCL_VERSION", "TCL_PATCH_LEVEL"]
+//! ```
+//! 
+//! Some libraries ship a script telling how to use them instead of, or besides, a
+//! .pc file. If `pkg-config` fails to probe the library, or is disabled for it, the
+//! first one of `config-script` found in `PATH` is run with `--cflags`, `--libs` and
+//! `--version`.
+//! 
+//! Otherwise the shell script named in `config-file` is looked for in the lib dirs
+//! of the install prefixes searched for the library, and in their subdirectories
+//! named in `includedir`, e.g. "/usr/lib/tcl8.6/tclConfig.sh". The variables listed
+//! in `cflags`, `libs` and `version` are read from it, the last being concatenated,
+//! e.g. "8.6" and ".13" into "8.6.13". The ones in `libs-private` are linked too when
+//! linking statically. Either way, the include dir is the first "-I" of the cflags.
+//! A script or file of a version not meeting the requirement is skipped.
+//! 
+//! ## CMake packages
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib.spec.zlib]
+//! cmake = { package = "ZLIB", targets = ["ZLIB::ZLIB"] }
+//! ```
+//! 
+//! Libraries installing only CMake package config files, e.g. "FooConfig.cmake", are
+//! probed by a locally installed cmake, or the one in `CMAKE`, if `pkg-config` fails
+//! to probe the library or is disabled for it. A throwaway project in `OUT_DIR` calls
+//! `find_package()` with the `package` and its optional `components`, then collects
+//! the include directories, compile definitions and link libraries of the `targets`
+//! and of the targets they link, recursively. Without `targets`, the variables of
+//! old-style packages such as `ZLIB_INCLUDE_DIRS` and `ZLIB_LIBRARIES` are used.
+//! `CMAKE_PREFIX_PATH` tells cmake where else to look for packages.
+//! 
+//! ## Search dirs of the C compiler
+//! 
+//! If `pkg-config` fails to probe a library having `libs`, or is disabled for it, the
+//! library is looked for where the C compiler looks, before the install prefixes
+//! described below. The include dirs are told by `cc -E -v`, and the lib dirs by
+//! `cc -print-search-dirs`, preceded by the ones in `CPATH` and `C_INCLUDE_PATH`,
+//! and in `LIBRARY_PATH` respectively. The compiler is the one in `CC_<target>` or
+//! `CC`, or else "cc". The include dir is the first one, or its subdirectory named
+//! in `includedir`, containing all of the `headers`, and each of `libs` is linked
+//! from the first lib dir containing any of its files.
+//! 
 //! ## Enumerating possible executable file names
 //! 
 //! ```toml
@@ -92,6 +170,11 @@
 //! "../include/{some-dir-in-includedir}" and "../lib" respectively. Note that
 //! "wish86.exe" and "wish.exe" are not necessary for Windows.
 //! 
+//! On Unix, "/usr/local", "/usr" and the directories in "/opt" are searched as
+//! well, e.g. "/opt/tcltk", if they contain all of the `headers`. Besides "lib",
+//! libraries are looked for in the multiarch directory of the target, e.g.
+//! "lib/x86_64-linux-gnu", and "lib64".
+//! 
 //! ## Enumerating possible include paths
 //! 
 //! ```toml
@@ -104,6 +187,23 @@
 //! exists, the include path will be expected to be "../include". It is optional,
 //! only used if `pkg-config` is missing or failed to probe library.
 //! 
+//! ## Layout of install prefixes
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib.spec.tk86]
+//! bindir = ["bin"]
+//! libdir = ["lib/<triple>", "lib64", "lib"]
+//! include-root = ["include"]
+//! ```
+//! 
+//! These values, relative to the install prefix, are the defaults. `bindir` lists
+//! the possible directories of the executable, from which the prefix is derived. An
+//! executable found elsewhere is skipped, and the next one in `exe` is tried.
+//! `libdir` lists the directories of libraries in order of preference, in which
+//! "<triple>" stands for the multiarch directory of the target, e.g.
+//! "x86_64-linux-gnu". `include-root` lists the directories where the ones in
+//! `includedir` are looked for.
+//! 
 //! ## Importing extra include paths
 //! 
 //! ```toml
@@ -145,6 +245,341 @@
 //! means that the metadata will be collected by inwelling if and only if feature
 //! "libtk" is enabled.
 //! 
+//! ## Libraries without spec
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib]
+//! build = ["zlib", { name = "lzma", headers = ["lzma.h"] }]
+//! ```
+//! 
+//! A library in `build` without a spec is probed by pkg-config only. Its headers may
+//! be given in `build` as above, which is a shorthand for a spec with `headers`
+//! only. Otherwise they are guessed from the include dir of its .pc file: "zlib.h"
+//! or "zlib/zlib.h" for "zlib", or else all the headers in the include dir if the
+//! library has an include dir of its own, such as "/usr/include/tcl8.6". Headers
+//! are not guessed for dependencies without a spec, which are linked only.
+//! 
+//! ## Version requirements
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib.spec.tcl86]
+//! version = ">=8.6.10, <9"
+//! version-macro = "TCL_PATCH_LEVEL"
+//! version-args = ["--version"]
+//! 
+//! [package.metadata.inwelling.clib.spec.tk86.dependencies]
+//! tcl86 = { version = ">=8.6" }
+//! ```
+//! 
+//! The value of `version` is a list of comma separated comparisons, each of which is
+//! one of `>=`, `>`, `<=`, `<` and `=` followed by a version. A version without
+//! comparison operator means `>=`. The requirements of a library's own spec and of
+//! its dependents' `dependencies` tables must all be met.
+//! 
+//! `pkg-config` checks the version in the .pc file. When the library is located by
+//! searching, its version is read from the macro named `version-macro` in its
+//! headers, or from the output of its executable run with `version-args`. Failure
+//! to meet the requirement is reported with the version found and the version
+//! required.
+//! 
+//! ## Configuring bindgen
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib.spec.tk86.bindgen]
+//! allowlist-function = ["Tk_.*"]
+//! blocklist-type = ["XEvent"]
+//! opaque-type = ["Tk_FakeWin"]
+//! enum-style = "rust"
+//! derive = ["Debug", "Default", "Copy"]
+//! size_t-is-usize = true
+//! layout-tests = false
+//! doc-comments = true
+//! ```
+//! 
+//! This optional section configures bindgen for the headers of tk86. The keys
+//! `allowlist-function`, `allowlist-type`, `allowlist-var`, `blocklist-function`,
+//! `blocklist-type`, `blocklist-var` and `opaque-type` accept regular expressions.
+//! The value of `enum-style` is one of "rust", "rust_non_exhaustive", "bitfield",
+//! "consts", "moduleconsts", "newtype" and "newtype_global". The value of `derive`
+//! is the complete set of derived traits, chosen from "Debug", "Default", "Copy",
+//! "Hash", "PartialEq", "Eq", "PartialOrd" and "Ord".
+//! 
+//! The regular expressions of `blocklist-var` apply to constants, i.e. macros, and
+//! to extern variables, but not to the functions or types matching them. Static
+//! variables defined in headers are not blocked.
+//! 
+//! All libraries in the root namespace share one bindgen run, so their options
+//! apply to the whole run, except `allowlist-path`, of which the files are joined.
+//! A list of item names, e.g. a `blocklist-type` of one library, would block the
+//! types of the others too, so two of these libraries having different lists fail
+//! the build, as do those setting `enum-style`, `derive`, `size_t-is-usize`,
+//! `layout-tests` or `doc-comments` differently. Libraries without spec count as
+//! having no lists. Use per-library modules, described below, to apply all the
+//! options to one library's headers only.
+//! 
+//! ## Restricting items to the library's headers
+//! 
+//! By default, the bindings of a library contain only the items declared in its
+//! `headers`, and in other files of their directories below the include root, e.g.
+//! "/usr/include/tcl8.6" for "tcl.h" found there, but not "/usr/include", which is
+//! shared with libc and other libraries. The include root is the nearest directory
+//! ending with any of `include-root`, by default "include", containing the include
+//! directory of the library, i.e. the "includedir" of its .pc file, or the one
+//! guessed from `exe` and `includedir`. Types from other headers which these items
+//! refer to are still generated. A library having `allowlist-function`,
+//! `allowlist-type` or `allowlist-var` is restricted by these lists instead. A
+//! library resolved without an include directory, e.g. by cmake or a config script,
+//! is not restricted.
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib.spec.tk86.bindgen]
+//! allowlist-path = ["/usr/include/X11/X.h", "../X11"]
+//! ```
+//! 
+//! The value of `allowlist-path` adds extra files, or directories of files, whose
+//! items are generated as well. Relative paths are relative to the include
+//! directory of the library.
+//! 
+//! ## Order of probing
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib.spec.tk86]
+//! vendored = "vendor/tk"
+//! probe = ["vendored", "pkg-config"]
+//! ```
+//! 
+//! A library is probed by the following strategies in turn, until one of them
+//! succeeds:
+//! 
+//! - "env", the environment variables described below.
+//! 
+//! - "pkg-config", the .pc file of the library.
+//! 
+//! - "cmake", the CMake package of the library.
+//! 
+//! - "config-script", the `config-script` or `config-file` of the library.
+//! 
+//! - "compiler", the search dirs of the C compiler.
+//! 
+//! - "search", the install prefixes of `exe` and the standard ones.
+//! 
+//! - "vendored", the install prefix of a copy shipped with the downstream crate,
+//!   relative to its manifest dir, laid out as `bindir`, `libdir` and `include-root`
+//!   describe.
+//! 
+//! The value of `probe` lists the strategies to try for the library, in order. For
+//! example, the spec above prefers the vendored copy of tk to the system one, and
+//! never searches for it elsewhere. Strategies not applicable to the library, e.g.
+//! "cmake" without a `cmake` package, are skipped. The environment variable
+//! `CLIB_PROBE_ORDER`, e.g. "vendored,pkg-config", takes precedence over `probe` of
+//! every library, so as to forbid looking for libraries in the system entirely.
+//! 
+//! ## Overriding by environment variables
+//! 
+//! The following environment variables, where `<NAME>` is the library name in
+//! upper case with other characters than letters and digits replaced by `_`, e.g.
+//! `TK86`, take precedence over the metadata:
+//! 
+//! - `CLIB_<NAME>_PREFIX`, the install prefix, with headers in `include` and
+//!   libraries in `lib`.
+//! 
+//! - `CLIB_<NAME>_INCLUDE_DIR` and `CLIB_<NAME>_LIB_DIR`, overriding the directories
+//!   derived from the prefix.
+//! 
+//! - `CLIB_<NAME>_NO_PKG_CONFIG`, not to use pkg-config for the library.
+//! 
+//! - `CLIB_<NAME>_STATIC`, to link static archives of the library.
+//! 
+//! If the prefix or the lib dir is given for a library with `libs` in its spec, the
+//! library is neither probed by pkg-config nor searched, and the files listed in
+//! `libs` are linked from the lib dir, failing the library if they are not found
+//! there. Otherwise, e.g. with the include dir only, or for a library without
+//! spec, the directories are ignored and the library is probed as usual.
+//! Changing any of these variables causes the library to be probed again.
+//! 
+//! ## Required and optional libraries
+//! 
+//! By default, a library failing to be probed is reported by cargo warnings, while
+//! the other libraries still get their bindings. Setting environment variable
+//! `CLIB_STRICT=1` turns the failure of any library in `build` into a build error,
+//! instead of a linker error later, unless the library is `optional`:
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib]
+//! build = ["tk86", { name = "tkimg", optional = true }]
+//! ```
+//! 
+//! A library asked by several packages is optional only if all of them say so.
+//! 
+//! A library with `required = true` in its spec fails the build whenever it fails
+//! to be probed, whether `CLIB_STRICT` is set or not, and whether it is in `build`
+//! or a dependency of some library in it.
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib.spec.tcl86]
+//! required = true
+//! ```
+//! 
+//! The build error comes with the failure reports of all the required libraries
+//! failing, after all the libraries have been probed and before bindgen runs.
+//! 
+//! ## Failure reports
+//! 
+//! A library failing to be probed is reported by cargo warnings, listing every
+//! probe strategy in order: why it was skipped, or why it failed and what it looked
+//! for, such as the .pc files tried and `PKG_CONFIG_PATH`, the executables looked
+//! for in `PATH`, the include dirs checked for `headers` and the lib dirs checked
+//! for `libs`. A library failing because of one of its dependencies is followed by
+//! the report of the dependency, e.g.
+//! 
+//! ```text
+//! fails to probe library tk86: ...
+//!   env: skipped, none of CLIB_TK86_PREFIX, CLIB_TK86_INCLUDE_DIR and CLIB_TK86_LIB_DIR is set
+//!   pkg-config: failed to locate tk86.pc or tk.pc
+//!     - tk86.pc: Package tk86 was not found in the pkg-config search path.
+//!     - tk.pc: Package tk was not found in the pkg-config search path.
+//!   ...
+//!   search: neither executable nor headers found
+//!     - wish86 not found in PATH
+//!     - wish not found in PATH
+//!     - /usr/local: headers ["tk.h"] not found in /usr/local/include
+//! ```
+//! 
+//! ## Metadata of resolved libraries
+//! 
+//! The libraries resolved, the built ones and their dependencies, are described by
+//! consts in module `clib::libs`, named after the libraries in upper case, with
+//! other characters than letters and digits replaced by `_`, e.g.
+//! `clib::libs::TK86`, or `clib::libs::LIBXML_2_0` for "libxml-2.0". They are
+//! listed in `clib::libs::ALL` with each library following its dependencies:
+//! 
+//! ```rust,no_run
+//! for lib in clib::libs::ALL {
+//!     println!( "{} {:?} found by {} in {}, linking {:?}",
+//!         lib.name, lib.version, lib.found_by, lib.found_in, lib.link_libs );
+//! }
+//! ```
+//! 
+//! Libraries of the same const name, e.g. "libxml-2.0" and "libxml_2_0", or of the
+//! name "all", fail the build.
+//! 
+//! Each `clib::libs::Lib` tells the name, the version if known, the .pc file if
+//! probed by pkg-config, the probe strategy and where it matched, the include dirs,
+//! the libraries linked, and `linked_statically`, whether a static archive of it is
+//! linked, as told by the `cargo:rustc-link-lib=static=` directives emitted.
+//! 
+//! The versions are also set as `cargo:rustc-env` variables while compiling clib,
+//! e.g. `CLIB_TK86_VERSION`, from which the consts read them.
+//! 
+//! ## cfgs of resolved libraries
+//! 
+//! Every library resolved, the built ones and their dependencies, sets a cfg named
+//! after it in lower case, e.g. `clib_tk86`, or `clib_libxml_2_0` for "libxml-2.0".
+//! Versions to tell apart are listed in `version-cfgs` of the spec:
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib.spec.tcl86]
+//! version-cfgs = ["8.6", "9.0"]
+//! ```
+//! 
+//! Each of them not newer than the version resolved sets a cfg too, e.g.
+//! `clib_tcl86_ge_8_6` for tcl 8.6.13, but not `clib_tcl86_ge_9_0`. All these cfgs,
+//! including the ones of libraries in specs failing to be resolved, are declared by
+//! `cargo:rustc-check-cfg`, so `#[cfg]` on them compiles without `unexpected_cfgs`
+//! warnings whether the library is resolved or not.
+//! 
+//! Like any cfg set by build scripts, they apply to compiling clib itself, including
+//! the bindings and `for-docs-rs` contents included in it. For downstream crates,
+//! clib passes them to their build scripts, space separated, in environment
+//! variables `DEP_CLIB_CFGS` and `DEP_CLIB_CHECK_CFGS`, which a build script may
+//! emit again by calling a helper like this one in its `main()`:
+//! 
+//! ```rust,no_run
+//! fn emit_clib_cfgs() {
+//!     let var = |name| std::env::var( name ).unwrap_or_default();
+//!     for cfg in var( "DEP_CLIB_CFGS" ).split_whitespace() {
+//!         println!( "cargo:rustc-cfg={}", cfg );
+//!     }
+//!     for cfg in var( "DEP_CLIB_CHECK_CFGS" ).split_whitespace() {
+//!         println!( "cargo:rustc-check-cfg=cfg({})", cfg );
+//!     }
+//! }
+//! ```
+//! 
+//! Only the versions in `version-cfgs` get cfgs. The versions resolved are passed
+//! too, e.g. "tcl86=8.6.13 tk86=8.6.13" in `DEP_CLIB_VERSIONS`, for build scripts
+//! to compare with others.
+//! 
+//! ## Probe reports
+//! 
+//! Every build writes `clib-report.json` in its `OUT_DIR`, e.g.
+//! `target/debug/build/clib-*/out/clib-report.json`, worth keeping as an artifact
+//! of CI. It records:
+//! 
+//! - the libraries to build, and the downstream packages and manifests asking for them.
+//! 
+//! - every library probed: how it matched, e.g. `"found_by": "pkg-config"`, and where,
+//!   e.g. `"found_in": "/usr/lib/x86_64-linux-gnu/pkgconfig/tk.pc"` or the executable
+//!   found in `PATH`, its version, include dirs, headers, clang args, link directives
+//!   and dependencies, and why it failed, with every probe strategy tried for it. A
+//!   library is probed again for a dependent requiring another version, so it may
+//!   fail for one version requirement, e.g. `"version_req": ">=1.3"`, and resolve for
+//!   others. A library not found at all, rather than found of another version, is
+//!   not probed again.
+//! 
+//! - every cargo directive emitted by clib, and the clang args passed to bindgen.
+//! 
+//! - the time taken by probing each library and by running bindgen, in milliseconds.
+//!   The report is written before bindgen runs too, with `"bindgen_millis": null`, in
+//!   case bindgen fails.
+//! 
+//! ## Building docs only
+//! 
+//! Docs may be built where the libraries are not installed, e.g. on docs.rs. Such
+//! builds are told by environment variable `DOCS_RS`, which docs.rs sets, or by
+//! `CLIB_DOCS_ONLY=1`. Libraries are neither probed nor linked then, and their
+//! bindings come from files shipped with the downstream crate, relative to its
+//! manifest:
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib.spec.tk86]
+//! for-docs-rs = "docs/tk86.rs"
+//! bundled-include-dir = "docs/include"
+//! ```
+//! 
+//! - `for-docs-rs`, bindings generated beforehand, e.g. a copy of the `bindings.rs`
+//!   or module file of the library from `OUT_DIR`. It is preferred, for not
+//!   requiring libclang.
+//! 
+//! - `bundled-include-dir`, the directory of the `headers` of the library, from which
+//!   bindgen generates the bindings. The dependencies of the library with bundled
+//!   headers of their own are resolved so too.
+//! 
+//! A library having neither is reported by a cargo warning, without failing the
+//! build, even if `CLIB_STRICT` is set or it is `required`. So are the libraries in
+//! a dependency cycle, none of which is resolved.
+//! 
+//! A library served from `for-docs-rs` sets its cfg, e.g. `clib_tk86`, as if it were
+//! resolved, and the cfgs of those `version-cfgs` which the `version` of its spec
+//! ensures, e.g. `clib_tk86_ge_8_6` for `version = ">=8.6"`.
+//! 
+//! Out of docs-only builds, the `for-docs-rs` contents of a library failing to be
+//! probed still make up its bindings, in case the docs are built anyway.
+//! 
+//! ## Checking of metadata
+//! 
+//! Metadata are checked before any library is probed. An unknown key such as
+//! `header = ["tk.h"]`, or a value of wrong type such as `headers = "tk.h"`, fails
+//! the build with an error naming the package, its manifest and the offending key,
+//! e.g. "unknown key `header`, did you mean `headers`?".
+//! 
+//! Several packages may specify the same library, as long as their specs are the
+//! same. Different specs of it fail the build with an error naming both packages
+//! and their manifests. Paths relative to the manifest, i.e. `vendored`,
+//! `for-docs-rs` and `bundled-include-dir`, are compared as written, so packages in
+//! different dirs may share a spec. The files are then taken from the first of
+//! these packages by name.
+//! 
 //! # Global namespace
 //! 
 //! All generated functions, types and constants are in the root namespace of this
@@ -154,6 +589,27 @@
 //! of tcl-sys and tk-sys generate `tcl_sys::Tcl_Init()` and `tk_sys::Tk_Init()`
 //! respectively.
 //! 
+//! # Per-library modules
+//! 
+//! ```toml
+//! [package.metadata.inwelling.clib]
+//! build = ["tk86"]
+//! modules = true
+//! ```
+//! 
+//! The value `modules = true` puts the bindings of each library into a module
+//! named after it, e.g. `clib::tk86::Tk_Init()` and `clib::tcl86::Tcl_Init()`, so
+//! that two libraries defining the same typedef or macro no longer collide. Each
+//! of `dependencies` and `header-dependencies` which has `headers` gets its own
+//! module too, and the items declared in its header files are imported by the
+//! dependent's module rather than generated again.
+//! 
+//! Since all downstream crates share one crate clib, the ones with `build` should
+//! agree on `modules`: if some set it but others do not, the build fails with an
+//! error naming them. The modules of libraries listed in `build` are
+//! re-exported from the root namespace, so `clib::Tk_Init()` keeps working unless
+//! the name is ambiguous.
+//! 
 //! # Caveat
 //! 
 //! ## Windows does not support pkg-config well
@@ -171,3 +627,5 @@
 #![allow(non_snake_case)]
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+pub mod libs;
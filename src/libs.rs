@@ -0,0 +1,26 @@
+//! Metadata of the libraries resolved by the build script: the built ones and their
+//! dependencies, e.g. `clib::libs::TCL86.version`, and all of them in `ALL`.
+
+/// A library as resolved by the build script.
+#[derive( Clone, Copy, Debug )]
+pub struct Lib {
+    /// The name in `build` or `dependencies`, e.g. "tcl86".
+    pub name              : &'static str,
+    /// The version told by the .pc file, config script, headers or executable, if any.
+    pub version           : Option<&'static str>,
+    /// The .pc file matched, e.g. "tcl" for tcl.pc, if probed by pkg-config.
+    pub pc_name           : Option<&'static str>,
+    /// The probe strategy matching the library, e.g. "pkg-config" or "search".
+    pub found_by          : &'static str,
+    /// Where the library matched, e.g. the path of the .pc file or the executable.
+    pub found_in          : &'static str,
+    /// The include dirs, the one holding its headers first.
+    pub include_dirs      : &'static [&'static str],
+    /// The libraries linked, e.g. "tcl8.6", in the order of linking.
+    pub link_libs         : &'static [&'static str],
+    /// Whether a static archive is linked for the library, i.e. a
+    /// `rustc-link-lib=static=` directive of it is emitted.
+    pub linked_statically : bool,
+}
+
+include!( concat!( env!( "OUT_DIR" ), "/libs.rs" ));